@@ -0,0 +1,72 @@
+//! Traditional names for the full moon of each month
+
+use serde::Deserialize;
+
+/// The naming tradition used for full moon names
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Culture {
+    /// Native American names as popularised by the Old Farmer's Almanac
+    #[default]
+    FarmersAlmanac,
+    Celtic,
+    /// Traditional Chinese month names. These belong to lunar months, so
+    /// keying them by calendar month is only an approximation.
+    Chinese,
+}
+
+const FARMERS_ALMANAC: [&str; 12] = [
+    "Wolf Moon",
+    "Snow Moon",
+    "Worm Moon",
+    "Pink Moon",
+    "Flower Moon",
+    "Strawberry Moon",
+    "Buck Moon",
+    "Sturgeon Moon",
+    "Corn Moon",
+    "Hunter's Moon",
+    "Beaver Moon",
+    "Cold Moon",
+];
+
+const CELTIC: [&str; 12] = [
+    "Quiet Moon",
+    "Moon of Ice",
+    "Moon of Winds",
+    "Growing Moon",
+    "Bright Moon",
+    "Moon of Horses",
+    "Moon of Calming",
+    "Dispute Moon",
+    "Singing Moon",
+    "Harvest Moon",
+    "Dark Moon",
+    "Cold Moon",
+];
+
+const CHINESE: [&str; 12] = [
+    "Holiday Moon",
+    "Apricot Moon",
+    "Peach Moon",
+    "Plum Moon",
+    "Pomegranate Moon",
+    "Lotus Moon",
+    "Orchid Moon",
+    "Osmanthus Moon",
+    "Chrysanthemum Moon",
+    "Good Moon",
+    "Winter Moon",
+    "Bitter Moon",
+];
+
+/// Name of the full moon in `month`, numbered from 1 for January
+pub fn full_moon_name(culture: Culture, month: u32) -> &'static str {
+    let names = match culture {
+        Culture::FarmersAlmanac => &FARMERS_ALMANAC,
+        Culture::Celtic => &CELTIC,
+        Culture::Chinese => &CHINESE,
+    };
+
+    names[(month as usize - 1) % 12]
+}
@@ -0,0 +1,104 @@
+//! Lunar eclipses from a table of one Saros cycle, 2013 to 2031, projected
+//! backwards or forwards by whole Saros periods for other years. Eclipses
+//! recur a Saros apart, but each series slowly drifts and eventually ends,
+//! so the further from the table the rougher the prediction.
+
+/// Length of the Saros cycle, 6585.3211 days, in seconds
+const SAROS_SECONDS: f64 = 6585.3211 * 86400.0;
+
+/// How long either side of greatest eclipse the Moon is in the Earth's
+/// shadow, roughly half a typical penumbral phase
+const ECLIPSE_HALF_WINDOW_SECONDS: i64 = 2 * 3600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EclipseKind {
+    /// Only the faint outer shadow touches the Moon
+    Penumbral,
+    Partial,
+    Total,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LunarEclipse {
+    /// Unix timestamp of greatest eclipse
+    pub greatest: i64,
+    pub kind: EclipseKind,
+}
+
+/// Time of greatest eclipse, UT
+#[rustfmt::skip]
+static ECLIPSES: [(i64, EclipseKind); 41] = [
+    (1_366_920_420, EclipseKind::Partial), // 2013-04-25 20:07
+    (1_369_455_000, EclipseKind::Penumbral), // 2013-05-25 04:10
+    (1_382_140_200, EclipseKind::Penumbral), // 2013-10-18 23:50
+    (1_397_547_960, EclipseKind::Total), // 2014-04-15 07:46
+    (1_412_765_700, EclipseKind::Total), // 2014-10-08 10:55
+    (1_428_148_800, EclipseKind::Total), // 2015-04-04 12:00
+    (1_443_408_420, EclipseKind::Total), // 2015-09-28 02:47
+    (1_458_733_620, EclipseKind::Penumbral), // 2016-03-23 11:47
+    (1_474_052_040, EclipseKind::Penumbral), // 2016-09-16 18:54
+    (1_486_773_840, EclipseKind::Penumbral), // 2017-02-11 00:44
+    (1_502_130_000, EclipseKind::Partial), // 2017-08-07 18:20
+    (1_517_405_400, EclipseKind::Total), // 2018-01-31 13:30
+    (1_532_722_920, EclipseKind::Total), // 2018-07-27 20:22
+    (1_548_047_520, EclipseKind::Total), // 2019-01-21 05:12
+    (1_563_312_660, EclipseKind::Partial), // 2019-07-16 21:31
+    (1_578_683_400, EclipseKind::Penumbral), // 2020-01-10 19:10
+    (1_591_385_100, EclipseKind::Penumbral), // 2020-06-05 19:25
+    (1_593_923_460, EclipseKind::Penumbral), // 2020-07-05 04:31
+    (1_606_729_380, EclipseKind::Penumbral), // 2020-11-30 09:43
+    (1_622_027_940, EclipseKind::Total), // 2021-05-26 11:19
+    (1_637_312_580, EclipseKind::Partial), // 2021-11-19 09:03
+    (1_652_674_320, EclipseKind::Total), // 2022-05-16 04:12
+    (1_667_905_140, EclipseKind::Total), // 2022-11-08 10:59
+    (1_683_307_380, EclipseKind::Penumbral), // 2023-05-05 17:23
+    (1_698_524_040, EclipseKind::Partial), // 2023-10-28 20:14
+    (1_711_350_780, EclipseKind::Penumbral), // 2024-03-25 07:13
+    (1_726_627_440, EclipseKind::Partial), // 2024-09-18 02:44
+    (1_741_935_540, EclipseKind::Total), // 2025-03-14 06:59
+    (1_757_268_720, EclipseKind::Total), // 2025-09-07 18:12
+    (1_772_537_640, EclipseKind::Total), // 2026-03-03 11:34
+    (1_787_890_380, EclipseKind::Partial), // 2026-08-28 04:13
+    (1_803_165_180, EclipseKind::Penumbral), // 2027-02-20 23:13
+    (1_815_926_580, EclipseKind::Penumbral), // 2027-07-18 16:03
+    (1_818_486_840, EclipseKind::Penumbral), // 2027-08-17 07:14
+    (1_831_263_180, EclipseKind::Partial), // 2028-01-12 04:13
+    (1_846_520_400, EclipseKind::Partial), // 2028-07-06 18:20
+    (1_861_894_320, EclipseKind::Total), // 2028-12-31 16:52
+    (1_877_138_520, EclipseKind::Total), // 2029-06-26 03:22
+    (1_892_500_920, EclipseKind::Total), // 2029-12-20 22:42
+    (1_907_778_780, EclipseKind::Partial), // 2030-06-15 18:33
+    (1_923_085_620, EclipseKind::Penumbral), // 2030-12-09 22:27
+];
+
+/// The lunar eclipse in progress at `timestamp`, if any. Anyone who can see
+/// the Moon can see its eclipse, so a moonrise during one always shows it.
+pub fn eclipse_at(timestamp: i64) -> Option<LunarEclipse> {
+    ECLIPSES.iter().find_map(|&(greatest, kind)| {
+        let cycles = ((timestamp - greatest) as f64 / SAROS_SECONDS).round();
+        let greatest = greatest + (cycles * SAROS_SECONDS).round() as i64;
+
+        ((timestamp - greatest).abs() <= ECLIPSE_HALF_WINDOW_SECONDS)
+            .then_some(LunarEclipse { greatest, kind })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eclipse_at_finds_tabled_and_projected_eclipses() {
+        // 2024-09-18 02:44 UT, an hour before greatest eclipse
+        let partial = eclipse_at(1_726_627_440 - 3600).unwrap();
+        assert_eq!(partial.kind, EclipseKind::Partial);
+
+        // A Saros after 2015-09-28, the total eclipse of 2033-10-08 10:55 UT
+        let projected = eclipse_at(2_012_381_700).unwrap();
+        assert_eq!(projected.kind, EclipseKind::Total);
+        assert!((projected.greatest - 2_012_381_700).abs() < 30 * 60);
+
+        // The full moon of 2024-10-17, no eclipse
+        assert_eq!(eclipse_at(1_729_180_800), None);
+    }
+}
@@ -0,0 +1,285 @@
+//! Geocentric position of the Moon, following chapter 47 of Meeus'
+//! "Astronomical Algorithms" (accurate to about 10" in longitude).
+
+use geodate::delta_time::delta_time;
+
+/// Apparent geocentric position of the Moon
+#[derive(Debug, Clone, Copy)]
+pub struct LunarPosition {
+    /// Ecliptic longitude, in degrees
+    pub longitude: f64,
+    /// Ecliptic latitude, in degrees
+    pub latitude: f64,
+    /// Distance between the centres of the Earth and the Moon, in km
+    pub distance_km: f64,
+    /// Right ascension, in degrees
+    pub right_ascension: f64,
+    /// Declination, in degrees
+    pub declination: f64,
+}
+
+// Periodic terms for the longitude (Σl, 1e-6 degrees) and distance (Σr, 1e-3 km)
+//   D,  M,  M', F,      Σl,          Σr
+#[rustfmt::skip]
+const LONGITUDE_DISTANCE_TERMS: [(f64, f64, f64, f64, f64, f64); 60] = [
+    (0.0,  0.0,  1.0,  0.0, 6_288_774.0, -20_905_355.0),
+    (2.0,  0.0, -1.0,  0.0, 1_274_027.0,  -3_699_111.0),
+    (2.0,  0.0,  0.0,  0.0,   658_314.0,  -2_955_968.0),
+    (0.0,  0.0,  2.0,  0.0,   213_618.0,    -569_925.0),
+    (0.0,  1.0,  0.0,  0.0,  -185_116.0,      48_888.0),
+    (0.0,  0.0,  0.0,  2.0,  -114_332.0,      -3_149.0),
+    (2.0,  0.0, -2.0,  0.0,    58_793.0,     246_158.0),
+    (2.0, -1.0, -1.0,  0.0,    57_066.0,    -152_138.0),
+    (2.0,  0.0,  1.0,  0.0,    53_322.0,    -170_733.0),
+    (2.0, -1.0,  0.0,  0.0,    45_758.0,    -204_586.0),
+    (0.0,  1.0, -1.0,  0.0,   -40_923.0,    -129_620.0),
+    (1.0,  0.0,  0.0,  0.0,   -34_720.0,     108_743.0),
+    (0.0,  1.0,  1.0,  0.0,   -30_383.0,     104_755.0),
+    (2.0,  0.0,  0.0, -2.0,    15_327.0,      10_321.0),
+    (0.0,  0.0,  1.0,  2.0,   -12_528.0,           0.0),
+    (0.0,  0.0,  1.0, -2.0,    10_980.0,      79_661.0),
+    (4.0,  0.0, -1.0,  0.0,    10_675.0,     -34_782.0),
+    (0.0,  0.0,  3.0,  0.0,    10_034.0,     -23_210.0),
+    (4.0,  0.0, -2.0,  0.0,     8_548.0,     -21_636.0),
+    (2.0,  1.0, -1.0,  0.0,    -7_888.0,      24_208.0),
+    (2.0,  1.0,  0.0,  0.0,    -6_766.0,      30_824.0),
+    (1.0,  0.0, -1.0,  0.0,    -5_163.0,      -8_379.0),
+    (1.0,  1.0,  0.0,  0.0,     4_987.0,     -16_675.0),
+    (2.0, -1.0,  1.0,  0.0,     4_036.0,     -12_831.0),
+    (2.0,  0.0,  2.0,  0.0,     3_994.0,     -10_445.0),
+    (4.0,  0.0,  0.0,  0.0,     3_861.0,     -11_650.0),
+    (2.0,  0.0, -3.0,  0.0,     3_665.0,      14_403.0),
+    (0.0,  1.0, -2.0,  0.0,    -2_689.0,      -7_003.0),
+    (2.0,  0.0, -1.0,  2.0,    -2_602.0,           0.0),
+    (2.0, -1.0, -2.0,  0.0,     2_390.0,      10_056.0),
+    (1.0,  0.0,  1.0,  0.0,    -2_348.0,       6_322.0),
+    (2.0, -2.0,  0.0,  0.0,     2_236.0,      -9_884.0),
+    (0.0,  1.0,  2.0,  0.0,    -2_120.0,       5_751.0),
+    (0.0,  2.0,  0.0,  0.0,    -2_069.0,           0.0),
+    (2.0, -2.0, -1.0,  0.0,     2_048.0,      -4_950.0),
+    (2.0,  0.0,  1.0, -2.0,    -1_773.0,       4_130.0),
+    (2.0,  0.0,  0.0,  2.0,    -1_595.0,           0.0),
+    (4.0, -1.0, -1.0,  0.0,     1_215.0,      -3_958.0),
+    (0.0,  0.0,  2.0,  2.0,    -1_110.0,           0.0),
+    (3.0,  0.0, -1.0,  0.0,      -892.0,       3_258.0),
+    (2.0,  1.0,  1.0,  0.0,      -810.0,       2_616.0),
+    (4.0, -1.0, -2.0,  0.0,       759.0,      -1_897.0),
+    (0.0,  2.0, -1.0,  0.0,      -713.0,      -2_117.0),
+    (2.0,  2.0, -1.0,  0.0,      -700.0,       2_354.0),
+    (2.0,  1.0, -2.0,  0.0,       691.0,           0.0),
+    (2.0, -1.0,  0.0, -2.0,       596.0,           0.0),
+    (4.0,  0.0,  1.0,  0.0,       549.0,      -1_423.0),
+    (0.0,  0.0,  4.0,  0.0,       537.0,      -1_117.0),
+    (4.0, -1.0,  0.0,  0.0,       520.0,      -1_571.0),
+    (1.0,  0.0, -2.0,  0.0,      -487.0,      -1_739.0),
+    (2.0,  1.0,  0.0, -2.0,      -399.0,           0.0),
+    (0.0,  0.0,  2.0, -2.0,      -381.0,      -4_421.0),
+    (1.0,  1.0,  1.0,  0.0,       351.0,           0.0),
+    (3.0,  0.0, -2.0,  0.0,      -340.0,           0.0),
+    (4.0,  0.0, -3.0,  0.0,       330.0,           0.0),
+    (2.0, -1.0,  2.0,  0.0,       327.0,           0.0),
+    (0.0,  2.0,  1.0,  0.0,      -323.0,       1_165.0),
+    (1.0,  1.0, -1.0,  0.0,       299.0,           0.0),
+    (2.0,  0.0,  3.0,  0.0,       294.0,           0.0),
+    (2.0,  0.0, -1.0, -2.0,         0.0,       8_752.0),
+];
+
+// Periodic terms for the latitude (Σb, 1e-6 degrees)
+//   D,  M,  M', F,      Σb
+#[rustfmt::skip]
+const LATITUDE_TERMS: [(f64, f64, f64, f64, f64); 60] = [
+    (0.0,  0.0,  0.0,  1.0, 5_128_122.0),
+    (0.0,  0.0,  1.0,  1.0,   280_602.0),
+    (0.0,  0.0,  1.0, -1.0,   277_693.0),
+    (2.0,  0.0,  0.0, -1.0,   173_237.0),
+    (2.0,  0.0, -1.0,  1.0,    55_413.0),
+    (2.0,  0.0, -1.0, -1.0,    46_271.0),
+    (2.0,  0.0,  0.0,  1.0,    32_573.0),
+    (0.0,  0.0,  2.0,  1.0,    17_198.0),
+    (2.0,  0.0,  1.0, -1.0,     9_266.0),
+    (0.0,  0.0,  2.0, -1.0,     8_822.0),
+    (2.0, -1.0,  0.0, -1.0,     8_216.0),
+    (2.0,  0.0, -2.0, -1.0,     4_324.0),
+    (2.0,  0.0,  1.0,  1.0,     4_200.0),
+    (2.0,  1.0,  0.0, -1.0,    -3_359.0),
+    (2.0, -1.0, -1.0,  1.0,     2_463.0),
+    (2.0, -1.0,  0.0,  1.0,     2_211.0),
+    (2.0, -1.0, -1.0, -1.0,     2_065.0),
+    (0.0,  1.0, -1.0, -1.0,    -1_870.0),
+    (4.0,  0.0, -1.0, -1.0,     1_828.0),
+    (0.0,  1.0,  0.0,  1.0,    -1_794.0),
+    (0.0,  0.0,  0.0,  3.0,    -1_749.0),
+    (0.0,  1.0, -1.0,  1.0,    -1_565.0),
+    (1.0,  0.0,  0.0,  1.0,    -1_491.0),
+    (0.0,  1.0,  1.0,  1.0,    -1_475.0),
+    (0.0,  1.0,  1.0, -1.0,    -1_410.0),
+    (0.0,  1.0,  0.0, -1.0,    -1_344.0),
+    (1.0,  0.0,  0.0, -1.0,    -1_335.0),
+    (0.0,  0.0,  3.0,  1.0,     1_107.0),
+    (4.0,  0.0,  0.0, -1.0,     1_021.0),
+    (4.0,  0.0, -1.0,  1.0,       833.0),
+    (0.0,  0.0,  1.0, -3.0,       777.0),
+    (4.0,  0.0, -2.0,  1.0,       671.0),
+    (2.0,  0.0,  0.0, -3.0,       607.0),
+    (2.0,  0.0,  2.0, -1.0,       596.0),
+    (2.0, -1.0,  1.0, -1.0,       491.0),
+    (2.0,  0.0, -2.0,  1.0,      -451.0),
+    (0.0,  0.0,  3.0, -1.0,       439.0),
+    (2.0,  0.0,  2.0,  1.0,       422.0),
+    (2.0,  0.0, -3.0, -1.0,       421.0),
+    (2.0,  1.0, -1.0,  1.0,      -366.0),
+    (2.0,  1.0,  0.0,  1.0,      -351.0),
+    (4.0,  0.0,  0.0,  1.0,       331.0),
+    (2.0, -1.0,  1.0,  1.0,       315.0),
+    (2.0, -2.0,  0.0, -1.0,       302.0),
+    (0.0,  0.0,  1.0,  3.0,      -283.0),
+    (2.0,  1.0,  1.0, -1.0,      -229.0),
+    (1.0,  1.0,  0.0, -1.0,       223.0),
+    (1.0,  1.0,  0.0,  1.0,       223.0),
+    (0.0,  1.0, -2.0, -1.0,      -220.0),
+    (2.0,  1.0, -1.0, -1.0,      -220.0),
+    (1.0,  0.0,  1.0,  1.0,      -185.0),
+    (2.0, -1.0, -2.0, -1.0,       181.0),
+    (0.0,  1.0,  2.0,  1.0,      -177.0),
+    (4.0,  0.0, -2.0, -1.0,       176.0),
+    (4.0, -1.0, -1.0, -1.0,       166.0),
+    (1.0,  0.0,  1.0, -1.0,      -164.0),
+    (4.0,  0.0,  1.0, -1.0,       132.0),
+    (1.0,  0.0, -1.0, -1.0,      -119.0),
+    (4.0, -1.0,  0.0, -1.0,       115.0),
+    (2.0, -2.0,  0.0,  1.0,       107.0),
+];
+
+/// Julian centuries since J2000.0 in dynamical time, for a given Julian Date in UT
+pub fn julian_century(jd: f64) -> f64 {
+    let year = 2000.0 + (jd - 2_451_545.0) / 365.25;
+    let jde = jd + delta_time(year) / 86400.0;
+
+    (jde - 2_451_545.0) / 36525.0
+}
+
+/// Nutation in longitude and obliquity, in degrees, to about 0.5"
+pub fn nutation(t: f64) -> (f64, f64) {
+    let omega = (125.04452 - 1934.136261 * t).to_radians();
+    let sun = (280.4665 + 36000.7698 * t).to_radians();
+    let moon = (218.3165 + 481267.8813 * t).to_radians();
+
+    let longitude = -17.20 * omega.sin() - 1.32 * (2.0 * sun).sin() - 0.23 * (2.0 * moon).sin()
+        + 0.21 * (2.0 * omega).sin();
+    let obliquity = 9.20 * omega.cos() + 0.57 * (2.0 * sun).cos() + 0.10 * (2.0 * moon).cos()
+        - 0.09 * (2.0 * omega).cos();
+
+    (longitude / 3600.0, obliquity / 3600.0)
+}
+
+/// True obliquity of the ecliptic, in degrees
+pub fn obliquity(t: f64) -> f64 {
+    let mean = 23.439_291_1 - 0.013_004_2 * t - 1.64e-7 * t.powi(2) + 5.04e-7 * t.powi(3);
+
+    mean + nutation(t).1
+}
+
+/// Converts ecliptic coordinates to right ascension and declination, all in degrees
+pub fn ecliptic_to_equatorial(longitude: f64, latitude: f64, obliquity: f64) -> (f64, f64) {
+    let (lon, lat, eps) = (
+        longitude.to_radians(),
+        latitude.to_radians(),
+        obliquity.to_radians(),
+    );
+
+    let right_ascension = (lon.sin() * eps.cos() - lat.tan() * eps.sin()).atan2(lon.cos());
+    let declination = (lat.sin() * eps.cos() + lat.cos() * eps.sin() * lon.sin()).asin();
+
+    (
+        right_ascension.to_degrees().rem_euclid(360.0),
+        declination.to_degrees(),
+    )
+}
+
+/// Position of the Moon at the given Julian Date (UT)
+pub fn lunar_position(jd: f64) -> LunarPosition {
+    let t = julian_century(jd);
+
+    // Mean longitude of the Moon (L')
+    let l = 218.316_447_7 + 481_267.881_234_21 * t - 0.001_578_6 * t.powi(2)
+        + t.powi(3) / 538_841.0
+        - t.powi(4) / 65_194_000.0;
+    // Mean elongation of the Moon (D)
+    let d = 297.850_192_1 + 445_267.111_403_4 * t - 0.001_881_9 * t.powi(2) + t.powi(3) / 545_868.0
+        - t.powi(4) / 113_065_000.0;
+    // Mean anomaly of the Sun (M)
+    let m =
+        357.529_109_2 + 35_999.050_290_9 * t - 0.000_153_6 * t.powi(2) + t.powi(3) / 24_490_000.0;
+    // Mean anomaly of the Moon (M')
+    let mm = 134.963_396_4 + 477_198.867_505_5 * t + 0.008_741_4 * t.powi(2) + t.powi(3) / 69_699.0
+        - t.powi(4) / 14_712_000.0;
+    // Argument of latitude of the Moon (F)
+    let f =
+        93.272_095_0 + 483_202.017_523_3 * t - 0.003_653_9 * t.powi(2) - t.powi(3) / 3_526_000.0
+            + t.powi(4) / 863_310_000.0;
+
+    let a1 = 119.75 + 131.849 * t;
+    let a2 = 53.09 + 479_264.290 * t;
+    let a3 = 313.45 + 481_266.484 * t;
+
+    // Terms involving the Sun's mean anomaly shrink with the eccentricity of Earth's orbit
+    let e = 1.0 - 0.002_516 * t - 0.000_007_4 * t.powi(2);
+    let eccentricity = |m_coefficient: f64| e.powi(m_coefficient.abs() as i32);
+
+    let sin = |degrees: f64| degrees.to_radians().sin();
+    let cos = |degrees: f64| degrees.to_radians().cos();
+
+    let (mut sum_l, mut sum_r) = (0.0, 0.0);
+    for (cd, cm, cmm, cf, coefficient_l, coefficient_r) in LONGITUDE_DISTANCE_TERMS {
+        let argument = cd * d + cm * m + cmm * mm + cf * f;
+        sum_l += coefficient_l * eccentricity(cm) * sin(argument);
+        sum_r += coefficient_r * eccentricity(cm) * cos(argument);
+    }
+
+    let mut sum_b = 0.0;
+    for (cd, cm, cmm, cf, coefficient_b) in LATITUDE_TERMS {
+        let argument = cd * d + cm * m + cmm * mm + cf * f;
+        sum_b += coefficient_b * eccentricity(cm) * sin(argument);
+    }
+
+    // Venus, Jupiter and the flattening of the Earth
+    sum_l += 3958.0 * sin(a1) + 1962.0 * sin(l - f) + 318.0 * sin(a2);
+    sum_b += -2235.0 * sin(l)
+        + 382.0 * sin(a3)
+        + 175.0 * sin(a1 - f)
+        + 175.0 * sin(a1 + f)
+        + 127.0 * sin(l - mm)
+        - 115.0 * sin(l + mm);
+
+    let longitude = (l + sum_l / 1_000_000.0 + nutation(t).0).rem_euclid(360.0);
+    let latitude = sum_b / 1_000_000.0;
+    let distance_km = 385_000.56 + sum_r / 1000.0;
+    let (right_ascension, declination) = ecliptic_to_equatorial(longitude, latitude, obliquity(t));
+
+    LunarPosition {
+        longitude,
+        latitude,
+        distance_km,
+        right_ascension,
+        declination,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lunar_position_matches_meeus_example_47a() {
+        // 1992 April 12 at 0h TD, shifted back to UT by delta T
+        let jde = 2_448_724.5;
+        let jd = jde - delta_time(1992.28) / 86400.0;
+
+        let position = lunar_position(jd);
+        assert!((position.longitude - 133.167265).abs() < 0.001);
+        assert!((position.latitude - -3.229126).abs() < 0.001);
+        assert!((position.distance_km - 368_409.7).abs() < 1.0);
+        assert!((position.right_ascension - 134.688470).abs() < 0.001);
+        assert!((position.declination - 13.768368).abs() < 0.001);
+    }
+}
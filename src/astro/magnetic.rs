@@ -0,0 +1,165 @@
+//! Magnetic declination from the World Magnetic Model 2025, truncated to
+//! degree and order 6. The truncation leaves out the finer detail, so
+//! declinations are good to a degree or two away from the magnetic poles,
+//! enough to set a hiking or sailing compass but not for navigation.
+//!
+//! Each WMM is only valid for the five years after its epoch, when the next
+//! model replaces it, so there is no declination outside 2025 to 2029.
+
+/// Start of the model's epoch, 2025-01-01 0h UT, as a Julian Date
+const EPOCH_JD: f64 = 2_460_676.5;
+
+const DAYS_PER_YEAR: f64 = 365.25;
+
+/// How long after its epoch the model is valid for
+const VALIDITY_YEARS: f64 = 5.0;
+
+const DEGREE: usize = 6;
+
+/// WMM2025 Gauss coefficients `(n, m, g, h, g_dot, h_dot)`, in nT and nT/year
+#[rustfmt::skip]
+const COEFFICIENTS: [(usize, usize, f64, f64, f64, f64); 27] = [
+    (1, 0, -29_351.8,      0.0,  12.0,   0.0),
+    (1, 1,  -1_410.8,  4_545.4,   9.7, -21.5),
+    (2, 0,  -2_556.6,      0.0, -11.6,   0.0),
+    (2, 1,   2_951.1, -3_133.6,  -5.2, -27.7),
+    (2, 2,   1_649.3,   -815.1,  -8.0, -12.1),
+    (3, 0,   1_361.0,      0.0,  -1.3,   0.0),
+    (3, 1,  -2_404.1,    -56.6,  -4.2,   4.0),
+    (3, 2,   1_243.8,    237.5,   0.4,  -0.3),
+    (3, 3,     453.6,   -549.5, -15.6,  -4.1),
+    (4, 0,     895.0,      0.0,  -1.6,   0.0),
+    (4, 1,     799.5,    278.6,  -2.4,  -1.1),
+    (4, 2,      55.7,   -133.9,  -6.0,   4.1),
+    (4, 3,    -281.1,    212.0,   5.6,   1.6),
+    (4, 4,      12.1,   -375.6,  -7.0,  -4.4),
+    (5, 0,    -233.2,      0.0,   0.6,   0.0),
+    (5, 1,     368.9,     45.4,   1.4,  -0.5),
+    (5, 2,     187.2,    220.2,   0.0,   2.2),
+    (5, 3,    -138.7,   -122.9,   0.6,   0.4),
+    (5, 4,    -142.0,     43.0,   2.2,   1.7),
+    (5, 5,      20.9,    106.1,   0.9,   1.9),
+    (6, 0,      64.4,      0.0,  -0.2,   0.0),
+    (6, 1,      63.8,    -18.4,  -0.4,   0.3),
+    (6, 2,      76.9,     16.8,   0.9,  -1.6),
+    (6, 3,    -115.7,     48.8,   1.2,  -0.4),
+    (6, 4,     -40.9,    -59.8,  -0.9,   0.9),
+    (6, 5,      14.9,     10.9,   0.3,   0.7),
+    (6, 6,     -60.7,     72.7,   0.9,   0.9),
+];
+
+/// Angle between true north and magnetic north at `lat`/`lon` (east positive),
+/// in degrees. Positive when the compass points east of true north, negative
+/// when it points west. Treats the Earth as a sphere and the observer as at
+/// sea level. `None` when `jd` is outside the model's validity.
+pub fn magnetic_declination(jd: f64, lat: f64, lon: f64) -> Option<f64> {
+    let years = (jd - EPOCH_JD) / DAYS_PER_YEAR;
+    if !(0.0..VALIDITY_YEARS).contains(&years) {
+        return None;
+    }
+    let colatitude = (90.0 - lat).to_radians();
+    let (sin_theta, cos_theta) = colatitude.sin_cos();
+    let (p, dp) = schmidt_legendre(sin_theta, cos_theta);
+
+    // North and east components of the field, in nT
+    let (mut north, mut east) = (0.0, 0.0);
+    for (n, m, g, h, g_dot, h_dot) in COEFFICIENTS {
+        let g = g + g_dot * years;
+        let h = h + h_dot * years;
+        let (sin_m, cos_m) = (m as f64 * lon.to_radians()).sin_cos();
+
+        north += (g * cos_m + h * sin_m) * dp[n][m];
+        east += m as f64 * (g * sin_m - h * cos_m) * p[n][m];
+    }
+    // The east component is undefined at the geographic poles
+    east /= sin_theta.max(1e-9);
+
+    Some(east.atan2(north).to_degrees())
+}
+
+/// Schmidt semi-normalised associated Legendre functions of cos θ, and their
+/// derivatives with respect to θ
+fn schmidt_legendre(
+    sin_theta: f64,
+    cos_theta: f64,
+) -> (
+    [[f64; DEGREE + 1]; DEGREE + 1],
+    [[f64; DEGREE + 1]; DEGREE + 1],
+) {
+    let mut p = [[0.0; DEGREE + 1]; DEGREE + 1];
+    let mut dp = [[0.0; DEGREE + 1]; DEGREE + 1];
+    p[0][0] = 1.0;
+
+    // Gauss normalised recursion
+    for n in 1..=DEGREE {
+        for m in 0..=n {
+            if m == n {
+                p[n][n] = sin_theta * p[n - 1][n - 1];
+                dp[n][n] = sin_theta * dp[n - 1][n - 1] + cos_theta * p[n - 1][n - 1];
+            } else {
+                let (p2, dp2, k) = if n >= 2 {
+                    let k = ((n - 1).pow(2) as f64 - m.pow(2) as f64)
+                        / ((2 * n - 1) * (2 * n - 3)) as f64;
+                    (p[n - 2][m], dp[n - 2][m], k)
+                } else {
+                    (0.0, 0.0, 0.0)
+                };
+                p[n][m] = cos_theta * p[n - 1][m] - k * p2;
+                dp[n][m] = cos_theta * dp[n - 1][m] - sin_theta * p[n - 1][m] - k * dp2;
+            }
+        }
+    }
+
+    // Convert to Schmidt semi-normalisation
+    let mut scale = 1.0;
+    for n in 1..=DEGREE {
+        scale *= (2 * n - 1) as f64 / n as f64;
+        let mut factor = scale;
+        for m in 0..=n {
+            if m > 0 {
+                let doubled = if m == 1 { 2.0 } else { 1.0 };
+                factor *= ((n - m + 1) as f64 * doubled / (n + m) as f64).sqrt();
+            }
+            p[n][m] *= factor;
+            dp[n][m] *= factor;
+        }
+    }
+
+    (p, dp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn declination_matches_wmm2025_test_values() {
+        // The WMM2025 technical report's test values at sea level, east
+        // positive, with the error the truncation allows. It grows towards the
+        // poles, where the field's finer detail matters more.
+        for (years, lat, lon, expected, tolerance) in [
+            (0.0, 80.0, 0.0, 1.28, 2.5),
+            (0.0, 0.0, 120.0, -0.16, 0.5),
+            (0.0, -80.0, -120.0, 68.78, 2.5),
+            (2.5, 80.0, 0.0, 2.59, 2.5),
+            (2.5, 0.0, 120.0, -0.24, 0.5),
+            (2.5, -80.0, -120.0, 68.49, 2.5),
+        ] {
+            let jd = EPOCH_JD + years * DAYS_PER_YEAR;
+            let declination = magnetic_declination(jd, lat, lon).unwrap();
+            assert!(
+                (declination - expected).abs() < tolerance,
+                "{years}, {lat}, {lon}: {declination}"
+            );
+        }
+    }
+
+    #[test]
+    fn no_declination_outside_the_model() {
+        assert!(magnetic_declination(EPOCH_JD - 1.0, 40.0, -74.0).is_none());
+        assert!(magnetic_declination(EPOCH_JD, 40.0, -74.0).is_some());
+        let expiry = EPOCH_JD + VALIDITY_YEARS * DAYS_PER_YEAR;
+        assert!(magnetic_declination(expiry - 1.0, 40.0, -74.0).is_some());
+        assert!(magnetic_declination(expiry + 1.0, 40.0, -74.0).is_none());
+    }
+}
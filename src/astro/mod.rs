@@ -0,0 +1,479 @@
+//! Astronomical calculations that geodate doesn't provide
+
+pub mod cultural_names;
+pub mod eclipses;
+pub mod lunar_position;
+pub mod magnetic;
+pub mod moonrise;
+pub mod photography_score;
+pub mod refraction;
+pub mod solar_position;
+
+use geodate::moon_phase::{get_full_moon, get_lunation_number};
+use lunar_position::{lunar_position, LunarPosition};
+use solar_position::{solar_position, SolarPosition};
+
+/// Altitude of the Sun's centre at the start and end of astronomical twilight, in degrees
+pub const ASTRONOMICAL_TWILIGHT_ALTITUDE: f64 = -18.0;
+
+/// Period of the principal lunar semi-diurnal (M2) tide, in hours
+pub const M2_TIDAL_PERIOD_HOURS: f64 = 12.42;
+
+/// Below this altitude, in degrees, at transit the Moon never clears the
+/// worst of the atmospheric extinction and distortion
+pub const LOW_TRANSIT_ALTITUDE: f64 = 20.0;
+
+/// How close, in seconds, a moonrise has to be to a full moon to carry its name
+pub const FULL_MOON_WINDOW_SECONDS: i64 = 86400;
+
+/// Step used when scanning for horizon crossings, 10 minutes in days
+const CROSSING_SCAN_STEP: f64 = 10.0 / 1440.0;
+
+/// Mean distance of the Moon at perigee, in km
+pub const MEAN_PERIGEE_KM: f64 = 356_500.0;
+
+/// Mean distance of the Moon at apogee, in km
+pub const MEAN_APOGEE_KM: f64 = 406_700.0;
+
+/// Mean distance between the centres of the Earth and Moon, in km
+pub const MEAN_DISTANCE_KM: f64 = 384_400.0;
+
+/// Illuminance from a full Moon at its mean distance and high in a clear
+/// sky, in lux. Quoted values range from about 0.1 to 0.3 lux.
+pub const FULL_MOON_ILLUMINANCE_LUX: f64 = 0.25;
+
+/// How close, as a fraction, the Moon has to be to the mean perigee or apogee
+/// distance to be counted as being in that region
+pub const DISTANCE_CATEGORY_TOLERANCE: f64 = 0.05;
+
+/// The Moon's greatest declination, reached during a major lunar standstill, in degrees
+pub const MAJOR_STANDSTILL_DECLINATION: f64 = 28.725;
+
+/// Julian Date (UT) of a Unix timestamp
+pub fn unix_to_julian(timestamp: i64) -> f64 {
+    (timestamp as f64 / 86400.0) + 2440587.5
+}
+
+/// Modified Julian Date (UT) of a Unix timestamp, counted from 1858-11-17 00:00
+pub fn unix_to_mjd(timestamp: i64) -> f64 {
+    unix_to_julian(timestamp) - 2_400_000.5
+}
+
+pub fn julian_to_unix(jd: f64) -> i64 {
+    ((jd - 2440587.5) * 86400.0).round() as i64
+}
+
+/// Azimuth, in degrees east of north, at which a body with the given
+/// declination crosses the horizon when rising. `None` when the body never
+/// rises or never sets at this latitude.
+pub fn rising_azimuth(lat: f64, declination: f64) -> Option<f64> {
+    let cos_azimuth = declination.to_radians().sin() / lat.to_radians().cos();
+    if (-1.0..=1.0).contains(&cos_azimuth) {
+        Some(cos_azimuth.acos().to_degrees())
+    } else {
+        None
+    }
+}
+
+/// Qualitative category of the Earth-Moon distance: `perigee_region`,
+/// `apogee_region` or `average`
+pub fn distance_category(distance_km: f64) -> &'static str {
+    if distance_km <= MEAN_PERIGEE_KM * (1.0 + DISTANCE_CATEGORY_TOLERANCE) {
+        "perigee_region"
+    } else if distance_km >= MEAN_APOGEE_KM * (1.0 - DISTANCE_CATEGORY_TOLERANCE) {
+        "apogee_region"
+    } else {
+        "average"
+    }
+}
+
+/// Greenwich mean sidereal time, in degrees, at the given Julian Date (UT)
+pub fn sidereal_time(jd: f64) -> f64 {
+    let t = (jd - 2_451_545.0) / 36525.0;
+
+    (280.460_618_37 + 360.985_647_366_29 * (jd - 2_451_545.0) + 0.000_387_933 * t.powi(2)
+        - t.powi(3) / 38_710_000.0)
+        .rem_euclid(360.0)
+}
+
+/// Local mean sidereal time, in degrees, at longitude `lon` (east positive)
+pub fn local_sidereal_time(jd: f64, lon: f64) -> f64 {
+    (sidereal_time(jd) + lon).rem_euclid(360.0)
+}
+
+/// Local hour angle, in degrees between -180 and 180, of a body with the
+/// given right ascension. Negative east of the meridian, before transit.
+pub fn hour_angle(jd: f64, lon: f64, right_ascension: f64) -> f64 {
+    (local_sidereal_time(jd, lon) - right_ascension + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// Angle, in degrees, between the direction to the celestial pole and the
+/// zenith as seen at a body, see chapter 14 of Meeus
+pub fn parallactic_angle(lat: f64, hour_angle: f64, declination: f64) -> f64 {
+    let (lat, hour_angle, declination) = (
+        lat.to_radians(),
+        hour_angle.to_radians(),
+        declination.to_radians(),
+    );
+
+    hour_angle
+        .sin()
+        .atan2(lat.tan() * declination.cos() - declination.sin() * hour_angle.cos())
+        .to_degrees()
+}
+
+/// Azimuth, in degrees east of north, of a body with the given right
+/// ascension and declination seen from `lat`/`lon` (east positive)
+pub fn azimuth(jd: f64, lat: f64, lon: f64, right_ascension: f64, declination: f64) -> f64 {
+    let hour_angle = hour_angle(jd, lon, right_ascension).to_radians();
+    let (lat, declination) = (lat.to_radians(), declination.to_radians());
+
+    // Meeus measures westwards from the south
+    let from_south = hour_angle
+        .sin()
+        .atan2(hour_angle.cos() * lat.sin() - declination.tan() * lat.cos());
+    (from_south.to_degrees() + 180.0).rem_euclid(360.0)
+}
+
+/// Initial great circle bearing, in degrees east of north, from the first
+/// point to the second
+pub fn initial_bearing(from_lat: f64, from_lon: f64, to_lat: f64, to_lon: f64) -> f64 {
+    let (from_lat, to_lat) = (from_lat.to_radians(), to_lat.to_radians());
+    let delta_lon = (to_lon - from_lon).to_radians();
+
+    let y = delta_lon.sin() * to_lat.cos();
+    let x = from_lat.cos() * to_lat.sin() - from_lat.sin() * to_lat.cos() * delta_lon.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// Altitude above the horizon, in degrees, of a body with the given right
+/// ascension and declination seen from `lat`/`lon` (east positive)
+pub fn altitude(jd: f64, lat: f64, lon: f64, right_ascension: f64, declination: f64) -> f64 {
+    let hour_angle = hour_angle(jd, lon, right_ascension).to_radians();
+    let (lat, declination) = (lat.to_radians(), declination.to_radians());
+
+    (lat.sin() * declination.sin() + lat.cos() * declination.cos() * hour_angle.cos())
+        .asin()
+        .to_degrees()
+}
+
+/// Altitude of the Moon relative to where it appears on the horizon, so that
+/// moonrise and moonset are the zero crossings. Accounts for parallax,
+/// the almanac's 34' of refraction and the Moon's semi-diameter.
+pub fn moon_horizon_altitude(jd: f64, lat: f64, lon: f64) -> f64 {
+    refracted_moon_horizon_altitude(jd, lat, lon, refraction::ALMANAC_HORIZON_REFRACTION)
+}
+
+/// `moon_horizon_altitude` with `horizon_refraction` degrees of refraction
+pub fn refracted_moon_horizon_altitude(
+    jd: f64,
+    lat: f64,
+    lon: f64,
+    horizon_refraction: f64,
+) -> f64 {
+    let moon = lunar_position(jd);
+    let parallax = (6378.14 / moon.distance_km).asin().to_degrees();
+    let horizon = 0.7275 * parallax - horizon_refraction;
+
+    altitude(jd, lat, lon, moon.right_ascension, moon.declination) - horizon
+}
+
+/// Rounds a bearing to a tenth of a degree, from 0 up to but not including
+/// 360, so bearings just short of north round to 0
+pub fn round_bearing(degrees: f64) -> f64 {
+    ((degrees * 10.0).round() / 10.0).rem_euclid(360.0)
+}
+
+/// Formats a bearing in degrees as degrees, arcminutes and arcseconds, `112°34'56"`
+pub fn degrees_to_dms(degrees: f64) -> String {
+    let sign = if degrees < 0.0 { "-" } else { "" };
+    let seconds = (degrees.abs() * 3600.0).round() as u64;
+
+    format!(
+        "{}{}°{:02}'{:02}\"",
+        sign,
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+const CARDINAL_POINTS: [&str; 16] = [
+    "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW", "NW",
+    "NNW",
+];
+
+/// The nearest of the 16 compass points to a bearing in degrees east of north
+pub fn degrees_to_cardinal(degrees: f64) -> &'static str {
+    CARDINAL_POINTS[((degrees.rem_euclid(360.0) / 22.5).round() as usize) % 16]
+}
+
+/// Formats an angle in degrees as signed hours, minutes and seconds, `-3h41m12s`
+pub fn format_hours(degrees: f64) -> String {
+    let sign = if degrees < 0.0 { "-" } else { "" };
+    let seconds = (degrees.abs() / 15.0 * 3600.0).round() as u64;
+
+    format!(
+        "{}{}h{:02}m{:02}s",
+        sign,
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+/// Formats an angle in degrees as signed degrees and arcminutes, `+23°17'`
+pub fn format_degrees(degrees: f64) -> String {
+    let sign = if degrees < 0.0 { "-" } else { "+" };
+    let minutes = (degrees.abs() * 60.0).round() as u64;
+
+    format!("{}{}°{:02}'", sign, minutes / 60, minutes % 60)
+}
+
+/// Angle across the sky between the centres of the Moon and Sun, in degrees
+pub fn elongation(moon: &LunarPosition, sun: &SolarPosition) -> f64 {
+    let (moon_dec, sun_dec) = (moon.declination.to_radians(), sun.declination.to_radians());
+
+    (moon_dec.sin() * sun_dec.sin()
+        + moon_dec.cos()
+            * sun_dec.cos()
+            * (moon.right_ascension - sun.right_ascension)
+                .to_radians()
+                .cos())
+    .clamp(-1.0, 1.0)
+    .acos()
+    .to_degrees()
+}
+
+/// Fraction of the Moon's disc that is lit at the given elongation
+pub fn illuminated_fraction(elongation: f64) -> f64 {
+    (1.0 - elongation.to_radians().cos()) / 2.0
+}
+
+/// Whether the Moon is growing towards full
+pub fn is_waxing(moon: &LunarPosition, sun: &SolarPosition) -> bool {
+    (moon.longitude - sun.longitude).rem_euclid(360.0) < 180.0
+}
+
+/// Name of the Moon's phase, such as `Waxing gibbous moon`
+pub fn phase_name(illuminated_fraction: f64, waxing: bool) -> &'static str {
+    match (illuminated_fraction, waxing) {
+        (i, _) if i >= 0.97 => "Full moon",
+        (i, _) if i <= 0.03 => "New moon",
+        (i, true) if i >= 0.5 => "Waxing gibbous moon",
+        (i, false) if i >= 0.5 => "Waning gibbous moon",
+        (_, true) => "Waxing crescent moon",
+        (_, false) => "Waning crescent moon",
+    }
+}
+
+/// Rough illuminance from the Moon overhead, in lux, scaled by phase and by
+/// the inverse square of its distance. Ignores the opposition surge and the
+/// atmosphere.
+pub fn moon_illuminance_lux(jd: f64) -> f64 {
+    let moon = lunar_position(jd);
+    let fraction = illuminated_fraction(elongation(&moon, &solar_position(jd)));
+
+    FULL_MOON_ILLUMINANCE_LUX * fraction * (MEAN_DISTANCE_KM / moon.distance_km).powi(2)
+}
+
+/// Angle between the Sun and the Moon along the ecliptic, in degrees from 0
+/// at new moon to 180 at full moon
+pub fn sun_moon_angle(jd: f64) -> f64 {
+    let separation =
+        (lunar_position(jd).longitude - solar_position(jd).longitude).rem_euclid(360.0);
+
+    separation.min(360.0 - separation)
+}
+
+/// How far the Moon is through its synodic month, from 0 at new moon through
+/// 0.5 at full moon
+pub fn lunar_phase_fraction(jd: f64) -> f64 {
+    (lunar_position(jd).longitude - solar_position(jd).longitude).rem_euclid(360.0) / 360.0
+}
+
+/// The Unicode moon phase emoji, starting from new moon
+pub const MOON_PHASE_EMOJI: [&str; 8] = ["🌑", "🌒", "🌓", "🌔", "🌕", "🌖", "🌗", "🌘"];
+
+/// One symbol per day of the synodic month. Unicode only has the 8 phases,
+/// so the days between them are written as the pair of phases they fall between.
+pub const MOON_PHASE_EMOJI_29: [&str; 29] = [
+    "🌑", "🌑🌒", "🌑🌒", "🌒", "🌒", "🌒🌓", "🌒🌓", "🌓", "🌓", "🌓🌔", "🌔", "🌔", "🌔🌕",
+    "🌔🌕", "🌕", "🌕", "🌕🌖", "🌕🌖", "🌖", "🌖", "🌖🌗", "🌗", "🌗", "🌗🌘", "🌗🌘", "🌘", "🌘",
+    "🌘🌑", "🌘🌑",
+];
+
+/// Position, out of `positions`, of a phase fraction from `lunar_phase_fraction`,
+/// where the count wraps back round to new moon
+pub fn phase_index(phase_fraction: f64, positions: usize) -> usize {
+    (phase_fraction * positions as f64).round() as usize % positions
+}
+
+/// geodate numbers lunations from new moons, full moons fall half way through
+const FULL_MOON_LUNATION_OFFSET: f64 = 0.5;
+
+/// Unix timestamp of the full moon closest to `timestamp`
+pub fn nearest_full_moon(timestamp: i64) -> i64 {
+    let lunation = get_lunation_number(timestamp);
+
+    (-1..=1)
+        .map(|offset| get_full_moon(lunation + offset as f64 + FULL_MOON_LUNATION_OFFSET))
+        .min_by_key(|full_moon| (full_moon - timestamp).abs())
+        .unwrap()
+}
+
+pub fn sun_altitude(jd: f64, lat: f64, lon: f64) -> f64 {
+    let sun = solar_position(jd);
+
+    altitude(jd, lat, lon, sun.right_ascension, sun.declination)
+}
+
+/// First Julian Date within `window` days after `jd` at which `altitude`
+/// crosses `target` going upwards when `rising`, or downwards otherwise
+pub fn next_crossing(
+    jd: f64,
+    window: f64,
+    target: f64,
+    rising: bool,
+    altitude: impl Fn(f64) -> f64,
+) -> Option<f64> {
+    let crossed = |before: f64, after: f64| {
+        if rising {
+            before < target && after >= target
+        } else {
+            before >= target && after < target
+        }
+    };
+
+    let mut start = jd;
+    let mut start_altitude = altitude(start);
+    while start < jd + window {
+        let end = start + CROSSING_SCAN_STEP;
+        let end_altitude = altitude(end);
+        if crossed(start_altitude, end_altitude) {
+            // Bisect down to about a second
+            let (mut low, mut high) = (start, end);
+            while high - low > 1.0 / 86400.0 {
+                let middle = (low + high) / 2.0;
+                if crossed(start_altitude, altitude(middle)) {
+                    high = middle;
+                } else {
+                    low = middle;
+                }
+            }
+            return Some(high);
+        }
+        start = end;
+        start_altitude = end_altitude;
+    }
+
+    None
+}
+
+pub fn next_moonset(jd: f64, lat: f64, lon: f64) -> Option<f64> {
+    next_crossing(jd, 1.25, 0.0, false, |jd| {
+        moon_horizon_altitude(jd, lat, lon)
+    })
+}
+
+/// First upper transit of the Moon, when it crosses the local meridian at
+/// its highest, within a day and a bit after `jd`
+pub fn next_upper_transit(jd: f64, lon: f64) -> Option<f64> {
+    // The hour angle wraps from 180 to -180 at lower transit, so the only
+    // upward crossing of 0 is upper transit
+    next_crossing(jd, 1.1, 0.0, true, |jd| {
+        hour_angle(jd, lon, lunar_position(jd).right_ascension)
+    })
+}
+
+/// Geometric altitude, in degrees, of a body with `declination` as it
+/// crosses the meridian above `lat`
+pub fn meridian_altitude(lat: f64, declination: f64) -> f64 {
+    90.0 - (lat - declination).abs()
+}
+
+pub fn next_astronomical_dawn(jd: f64, lat: f64, lon: f64) -> Option<f64> {
+    next_crossing(jd, 1.0, ASTRONOMICAL_TWILIGHT_ALTITUDE, true, |jd| {
+        sun_altitude(jd, lat, lon)
+    })
+}
+
+/// The last time the sky became fully dark in the day before `jd`
+pub fn previous_astronomical_dusk(jd: f64, lat: f64, lon: f64) -> Option<f64> {
+    next_crossing(jd - 1.0, 1.0, ASTRONOMICAL_TWILIGHT_ALTITUDE, false, |jd| {
+        sun_altitude(jd, lat, lon)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_full_moon_finds_the_full_moon() {
+        // 2024-01-25 17:54 UT, from a few days before
+        let full_moon = nearest_full_moon(1_705_795_200);
+        assert!((full_moon - 1_706_205_240).abs() < 30 * 60, "{}", full_moon);
+    }
+
+    #[test]
+    fn meridian_altitude_follows_declination() {
+        assert_eq!(meridian_altitude(40.0, 10.0), 60.0);
+        assert_eq!(meridian_altitude(-30.0, 20.0), 40.0);
+        // Transits north of the zenith
+        assert_eq!(meridian_altitude(40.0, 60.0), 70.0);
+    }
+
+    #[test]
+    fn phase_index_wraps_to_new_moon() {
+        assert_eq!(phase_index(0.0, 29), 0);
+        assert_eq!(phase_index(0.5, 8), 4);
+        assert_eq!(MOON_PHASE_EMOJI_29[phase_index(0.5, 29)], "🌕");
+        assert_eq!(phase_index(0.99, 29), 0);
+    }
+
+    #[test]
+    fn converts_unix_timestamps_to_julian_dates() {
+        assert_eq!(unix_to_julian(0), 2_440_587.5);
+        // J2000.0, 2000-01-01 12:00 UTC, is JD 2451545.0 exactly
+        assert!((unix_to_julian(946_728_000) - 2_451_545.0).abs() < 1e-6);
+        assert_eq!(julian_to_unix(unix_to_julian(1_792_094_220)), 1_792_094_220);
+    }
+
+    #[test]
+    fn unix_epoch_is_mjd_40587() {
+        assert_eq!(unix_to_mjd(0), 40_587.0);
+    }
+
+    #[test]
+    fn formats_sexagesimal_angles() {
+        assert_eq!(format_hours(215.821), "14h23m17s");
+        assert_eq!(format_hours(-55.3), "-3h41m12s");
+        assert_eq!(format_degrees(23.2833), "+23°17'");
+        assert_eq!(format_degrees(-0.5), "-0°30'");
+        assert_eq!(degrees_to_dms(112.5822), "112°34'56\"");
+    }
+
+    #[test]
+    fn bearing_between_points() {
+        // Due east along the equator, and London to Paris
+        assert!((initial_bearing(0.0, 0.0, 0.0, 10.0) - 90.0).abs() < 1e-9);
+        assert!((initial_bearing(51.5074, -0.1278, 48.8566, 2.3522) - 148.1).abs() < 0.1);
+    }
+
+    #[test]
+    fn names_compass_points() {
+        assert_eq!(degrees_to_cardinal(0.0), "N");
+        assert_eq!(degrees_to_cardinal(112.5), "ESE");
+        assert_eq!(degrees_to_cardinal(350.0), "N");
+        assert_eq!(degrees_to_cardinal(-90.0), "W");
+    }
+
+    #[test]
+    fn bearings_round_below_360() {
+        assert_eq!(round_bearing(359.9996), 0.0);
+        assert_eq!(round_bearing(359.94), 359.9);
+        assert_eq!(round_bearing(-0.02), 0.0);
+        assert_eq!(round_bearing(112.64), 112.6);
+    }
+}
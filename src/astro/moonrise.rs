@@ -0,0 +1,202 @@
+//! Interchangeable ways of finding the moonrise on a given day
+
+use super::{
+    julian_to_unix, next_crossing, refracted_moon_horizon_altitude,
+    refraction::{RefractionModel, ALMANAC_HORIZON_REFRACTION},
+    unix_to_julian,
+};
+use geodate::moon_transit::get_moonrise;
+use serde::Deserialize;
+
+/// Finds the moonrise on the day starting at `timestamp`, 0h UT of the
+/// observer's date, if the Moon rises that day
+pub trait MoonriseCalculator {
+    fn moonrise(
+        &self,
+        timestamp: i64,
+        lat: f64,
+        lon: f64,
+        refraction: RefractionModel,
+    ) -> Option<i64>;
+}
+
+/// Refraction models differing from the almanac's by less than this, in
+/// degrees, leave geodate's moonrise as it is
+const NEGLIGIBLE_EXTRA_ALTITUDE: f64 = 1.0 / 60.0;
+
+/// Largest shift of geodate's moonrise for another refraction model, in
+/// seconds. A Moon grazing the horizon climbs too slowly to extrapolate by.
+const MAX_REFRACTION_SHIFT_SECONDS: f64 = 1800.0;
+
+/// geodate's moonrise, good to within about 5 minutes
+pub struct Approximate;
+
+impl MoonriseCalculator for Approximate {
+    fn moonrise(
+        &self,
+        timestamp: i64,
+        lat: f64,
+        lon: f64,
+        refraction: RefractionModel,
+    ) -> Option<i64> {
+        // geodate solves for the Moon's upper limb at the almanac's refracted
+        // horizon, corrected for parallax, so other models shift its answer by
+        // the extra altitude the Moon has to climb
+        let moonrise = get_moonrise(timestamp, lon, lat)?;
+        let extra_altitude = ALMANAC_HORIZON_REFRACTION - refraction.horizon_refraction();
+        if extra_altitude.abs() < NEGLIGIBLE_EXTRA_ALTITUDE {
+            return Some(moonrise);
+        }
+
+        let jd = unix_to_julian(moonrise);
+        let step = 60.0 / 86400.0;
+        let altitude = |jd| refracted_moon_horizon_altitude(jd, lat, lon, 0.0);
+        let degrees_per_second = (altitude(jd + step) - altitude(jd - step)) / 120.0;
+
+        if degrees_per_second.is_nan() || degrees_per_second <= 0.0 {
+            // Not climbing at geodate's rise, there's nothing to shift along
+            return Some(moonrise);
+        }
+        let shift = (extra_altitude / degrees_per_second)
+            .clamp(-MAX_REFRACTION_SHIFT_SECONDS, MAX_REFRACTION_SHIFT_SECONDS);
+
+        Some(moonrise + shift.round() as i64)
+    }
+}
+
+/// Root-finds the horizon crossing of the full Meeus lunar theory in
+/// `lunar_position`, good to within about 30 seconds
+pub struct HighPrecision;
+
+impl MoonriseCalculator for HighPrecision {
+    fn moonrise(
+        &self,
+        timestamp: i64,
+        lat: f64,
+        lon: f64,
+        refraction: RefractionModel,
+    ) -> Option<i64> {
+        // Search the observer's local mean solar day
+        let start = unix_to_julian(timestamp) - lon / 360.0;
+        let horizon_refraction = refraction.horizon_refraction();
+
+        next_crossing(start, 1.0, 0.0, true, |jd| {
+            refracted_moon_horizon_altitude(jd, lat, lon, horizon_refraction)
+        })
+        .map(julian_to_unix)
+    }
+}
+
+/// Which `MoonriseCalculator` a request uses
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EphemerisType {
+    #[default]
+    Approximate,
+    HighPrecision,
+}
+
+impl EphemerisType {
+    pub fn calculator(&self) -> &'static dyn MoonriseCalculator {
+        match self {
+            EphemerisType::Approximate => &Approximate,
+            EphemerisType::HighPrecision => &HighPrecision,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-03-01 0h UT
+    const MARCH_2024: i64 = 1_709_251_200;
+
+    #[test]
+    fn approximate_shifts_like_high_precision() {
+        let (lat, lon) = (51.5, -0.13);
+        // geodate's moonrise for a day is sometimes the previous day's, so
+        // compare it with whichever precise moonrise is closest
+        let nearest_precise = |moonrise: i64, refraction| {
+            (-1..=1)
+                .filter_map(|offset| {
+                    let day = moonrise - moonrise.rem_euclid(86400) + offset * 86400;
+                    HighPrecision.moonrise(day, lat, lon, refraction)
+                })
+                .min_by_key(|precise| (precise - moonrise).abs())
+                .unwrap()
+        };
+
+        for day in 0..30 {
+            let timestamp = MARCH_2024 + day * 86400;
+            let Some(approximate) =
+                Approximate.moonrise(timestamp, lat, lon, RefractionModel::Standard)
+            else {
+                continue;
+            };
+            let precise = nearest_precise(approximate, RefractionModel::Standard);
+
+            for refraction in [RefractionModel::Saemundsson, RefractionModel::None] {
+                let approximate_shift = Approximate
+                    .moonrise(timestamp, lat, lon, refraction)
+                    .unwrap()
+                    - approximate;
+                let precise_shift = nearest_precise(approximate, refraction) - precise;
+                assert!(
+                    (approximate_shift - precise_shift).abs() < 30,
+                    "{refraction:?} day {day}: {approximate_shift} {precise_shift}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn grazing_moonrises_shift_a_bounded_amount() {
+        // North of the Arctic circle the Moon skims the horizon on the days
+        // around when it stops rising at all. Without refraction it has
+        // further to climb, so it rises later.
+        for day in 0..60 {
+            let timestamp = MARCH_2024 + day * 86400;
+            let Some(standard) =
+                Approximate.moonrise(timestamp, 69.65, 18.96, RefractionModel::Standard)
+            else {
+                continue;
+            };
+            for refraction in [RefractionModel::Saemundsson, RefractionModel::None] {
+                let shifted = Approximate
+                    .moonrise(timestamp, 69.65, 18.96, refraction)
+                    .unwrap();
+                assert!(
+                    (0.0..=MAX_REFRACTION_SHIFT_SECONDS).contains(&((shifted - standard) as f64)),
+                    "{refraction:?} day {day}: {shifted} {standard}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn high_precision_matches_reference_moonrises() {
+        // The reference moonrises geodate's own tests check against, as
+        // `(moonrise, 0h UT of the day, lat, lon)`
+        for (expected, day, lat, lon) in [
+            // 2000-01-01 01:50:14 UT
+            (946_691_414, 946_684_800, 0.0, 0.0),
+            // 2000-01-10 10:02:35 UT
+            (947_498_555, 947_462_400, 50.0, 0.0),
+            // 2015-06-21 09:12:30 UT
+            (1_434_877_950, 1_434_844_800, 45.0, 0.0),
+            // 2018-10-24 17:23:19 UT, at Stonehenge
+            (1_540_401_799, 1_540_339_200, 51.17883, -1.82619),
+            // 2025-10-18 03:27:53 UT
+            (1_760_758_073, 1_760_745_600, -4.0, 0.0),
+        ] {
+            let moonrise = HighPrecision
+                .moonrise(day, lat, lon, RefractionModel::Standard)
+                .unwrap();
+            assert!(
+                (moonrise - expected).abs() <= 30,
+                "{lat}, {lon}: {moonrise} {expected}"
+            );
+        }
+    }
+}
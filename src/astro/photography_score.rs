@@ -0,0 +1,190 @@
+//! Scores how photogenic a moonrise is, and explains the score
+
+use super::{
+    azimuth, degrees_to_cardinal, elongation, illuminated_fraction, is_waxing,
+    lunar_position::lunar_position, next_astronomical_dawn, next_crossing, phase_name,
+    solar_position::solar_position, sun_altitude, ASTRONOMICAL_TWILIGHT_ALTITUDE,
+};
+
+/// Sun altitudes, in degrees, between which the light is warm and soft
+const GOLDEN_HOUR_ALTITUDES: (f64, f64) = (-4.0, 6.0);
+
+/// How far, in degrees, the Sun can be from the middle of golden hour before
+/// the twilight factor drops to nothing
+const TWILIGHT_FALLOFF: f64 = 12.0;
+
+/// How much each factor contributes to the score, relative to the others
+#[derive(Debug, Clone, Copy)]
+pub struct PhotographyWeights {
+    /// How full the Moon is, defaults to 4
+    pub illumination: f64,
+    /// How close the Moon rises to opposite the Sun, defaults to 1
+    pub azimuth: f64,
+    /// Whether the Moon rises while the sky is in twilight, defaults to 3
+    pub twilight: f64,
+    /// How far the Moon is from the Sun's glare, defaults to 2
+    pub glare: f64,
+}
+
+impl Default for PhotographyWeights {
+    fn default() -> Self {
+        PhotographyWeights {
+            illumination: 4.0,
+            azimuth: 1.0,
+            twilight: 3.0,
+            glare: 2.0,
+        }
+    }
+}
+
+/// The sky at the moment of a moonrise
+#[derive(Debug, Clone, Copy)]
+pub struct MoonriseConditions {
+    /// Fraction of the Moon's disc that is lit, from 0 to 1
+    pub illumination: f64,
+    /// Angle between the Moon and the Sun, in degrees from 0 to 180
+    pub elongation: f64,
+    /// Whether the Moon is growing towards full
+    pub waxing: bool,
+    pub moon_azimuth: f64,
+    pub sun_azimuth: f64,
+    pub sun_altitude: f64,
+}
+
+impl MoonriseConditions {
+    pub fn at(jd: f64, lat: f64, lon: f64) -> Self {
+        let (moon, sun) = (lunar_position(jd), solar_position(jd));
+        let elongation = elongation(&moon, &sun);
+
+        MoonriseConditions {
+            illumination: illuminated_fraction(elongation),
+            elongation,
+            waxing: is_waxing(&moon, &sun),
+            moon_azimuth: azimuth(jd, lat, lon, moon.right_ascension, moon.declination),
+            sun_azimuth: azimuth(jd, lat, lon, sun.right_ascension, sun.declination),
+            sun_altitude: sun_altitude(jd, lat, lon),
+        }
+    }
+
+    /// 1 in the middle of golden hour
+    fn twilight(&self) -> f64 {
+        let (low, high) = GOLDEN_HOUR_ALTITUDES;
+        let from_middle = (self.sun_altitude - (low + high) / 2.0).abs();
+
+        (1.0 - from_middle / TWILIGHT_FALLOFF).max(0.0)
+    }
+
+    /// 1 when the Moon rises exactly opposite the Sun
+    fn opposition(&self) -> f64 {
+        (1.0 + (self.moon_azimuth - self.sun_azimuth - 180.0)
+            .to_radians()
+            .cos())
+            / 2.0
+    }
+
+    fn glare(&self) -> f64 {
+        (self.elongation / 90.0).min(1.0)
+    }
+
+    fn sky_name(&self) -> &'static str {
+        let (golden_low, golden_high) = GOLDEN_HOUR_ALTITUDES;
+        if (golden_low..=golden_high).contains(&self.sun_altitude) {
+            "at golden hour"
+        } else if self.sun_altitude > golden_high {
+            "in daylight"
+        } else if self.sun_altitude > ASTRONOMICAL_TWILIGHT_ALTITUDE {
+            "in twilight"
+        } else {
+            "in darkness"
+        }
+    }
+}
+
+/// How photogenic a moonrise is, from 0 to 10
+pub fn photography_score(conditions: &MoonriseConditions, weights: &PhotographyWeights) -> f64 {
+    let weighted = [
+        (weights.illumination, conditions.illumination),
+        (weights.azimuth, conditions.opposition()),
+        (weights.twilight, conditions.twilight()),
+        (weights.glare, conditions.glare()),
+    ];
+    let total_weight: f64 = weighted.iter().map(|(weight, _)| weight).sum();
+    if total_weight <= 0.0 {
+        return 0.0;
+    }
+
+    10.0 * weighted
+        .iter()
+        .map(|(weight, factor)| weight * factor)
+        .sum::<f64>()
+        / total_weight
+}
+
+/// A one word summary of a score from `photography_score`
+pub fn rating(score: f64) -> &'static str {
+    match score {
+        s if s >= 8.0 => "excellent",
+        s if s >= 6.0 => "good",
+        s if s >= 4.0 => "fair",
+        _ => "poor",
+    }
+}
+
+/// Length, in days, of the astronomical darkness in the night around the
+/// moonrise at `jd`. `None` when the sky never gets fully dark.
+pub fn darkness_window(jd: f64, lat: f64, lon: f64) -> Option<f64> {
+    let dusk = next_crossing(jd - 0.5, 1.0, ASTRONOMICAL_TWILIGHT_ALTITUDE, false, |jd| {
+        sun_altitude(jd, lat, lon)
+    })?;
+
+    Some(next_astronomical_dawn(dusk, lat, lon)? - dusk)
+}
+
+/// `Score 9.2: Full moon rising at golden hour, azimuth 105° ESE, total darkness window 5h23m`
+pub fn explain(score: f64, conditions: &MoonriseConditions, darkness: Option<f64>) -> String {
+    let darkness = match darkness {
+        Some(days) => {
+            let minutes = (days * 1440.0).round() as i64;
+            format!(
+                "total darkness window {}h{:02}m",
+                minutes / 60,
+                minutes % 60
+            )
+        }
+        None => "no astronomical darkness".to_string(),
+    };
+
+    format!(
+        "Score {:.1}: {} rising {}, azimuth {:.0}° {}, {}",
+        score,
+        phase_name(conditions.illumination, conditions.waxing),
+        conditions.sky_name(),
+        conditions.moon_azimuth,
+        degrees_to_cardinal(conditions.moon_azimuth),
+        darkness
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_moon_opposite_a_setting_sun_scores_highly() {
+        let conditions = MoonriseConditions {
+            illumination: 1.0,
+            elongation: 180.0,
+            waxing: true,
+            moon_azimuth: 105.0,
+            sun_azimuth: 285.0,
+            sun_altitude: 1.0,
+        };
+        let score = photography_score(&conditions, &PhotographyWeights::default());
+
+        assert!((score - 10.0).abs() < 1e-9);
+        assert_eq!(
+            explain(score, &conditions, Some(323.0 / 1440.0)),
+            "Score 10.0: Full moon rising at golden hour, azimuth 105° ESE, total darkness window 5h23m"
+        );
+    }
+}
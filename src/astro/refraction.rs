@@ -0,0 +1,65 @@
+//! Atmospheric refraction at the horizon, following chapter 16 of Meeus'
+//! "Astronomical Algorithms". All models assume 1010 mbar and 10°C.
+
+use serde::Deserialize;
+
+/// The conventional 34' of horizon refraction used by almanacs, and by geodate
+pub const ALMANAC_HORIZON_REFRACTION: f64 = 34.0 / 60.0;
+
+/// How the rise time accounts for the atmosphere bending light over the horizon
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RefractionModel {
+    /// Bennett's formula, from the apparent altitude
+    #[default]
+    Standard,
+    /// Saemundsson's formula, from the true altitude, slightly more accurate
+    /// close to the horizon
+    Saemundsson,
+    /// The geometric horizon, without refraction
+    None,
+}
+
+impl RefractionModel {
+    /// How far below the geometric horizon, in degrees, a body is when it
+    /// appears on the horizon
+    pub fn horizon_refraction(&self) -> f64 {
+        match self {
+            RefractionModel::Standard => bennett(0.0),
+            RefractionModel::Saemundsson => {
+                // Saemundsson works from the true altitude, which at the
+                // horizon is minus the refraction itself
+                (0..10).fold(ALMANAC_HORIZON_REFRACTION, |refraction, _| {
+                    saemundsson(-refraction)
+                })
+            }
+            RefractionModel::None => 0.0,
+        }
+    }
+}
+
+/// Refraction, in degrees, of a body seen at `apparent_altitude` degrees
+pub fn bennett(apparent_altitude: f64) -> f64 {
+    let h = apparent_altitude;
+    1.0 / (h + 7.31 / (h + 4.4)).to_radians().tan() / 60.0
+}
+
+/// Refraction, in degrees, of a body whose geometric altitude is `true_altitude` degrees
+pub fn saemundsson(true_altitude: f64) -> f64 {
+    let h = true_altitude;
+    1.02 / (h + 10.3 / (h + 5.11)).to_radians().tan() / 60.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn horizon_refraction_is_about_half_a_degree() {
+        let arcminutes = |model: RefractionModel| model.horizon_refraction() * 60.0;
+
+        assert!((arcminutes(RefractionModel::Standard) - 34.5).abs() < 0.1);
+        assert!((arcminutes(RefractionModel::Saemundsson) - 34.5).abs() < 0.5);
+        assert_eq!(arcminutes(RefractionModel::None), 0.0);
+    }
+}
@@ -0,0 +1,64 @@
+//! Geocentric position of the Sun, following the low accuracy method in
+//! chapter 25 of Meeus' "Astronomical Algorithms" (accurate to about 0.01°).
+
+use super::lunar_position::{ecliptic_to_equatorial, julian_century, obliquity};
+
+/// Apparent geocentric position of the Sun
+#[derive(Debug, Clone, Copy)]
+pub struct SolarPosition {
+    /// Ecliptic longitude, in degrees
+    pub longitude: f64,
+    /// Distance between the centres of the Earth and the Sun, in AU
+    pub distance_au: f64,
+    /// Right ascension, in degrees
+    pub right_ascension: f64,
+    /// Declination, in degrees
+    pub declination: f64,
+}
+
+/// Position of the Sun at the given Julian Date (UT)
+pub fn solar_position(jd: f64) -> SolarPosition {
+    let t = julian_century(jd);
+
+    // Geometric mean longitude (L0) and mean anomaly (M)
+    let l0 = 280.466_46 + 36_000.769_83 * t + 0.000_303_2 * t.powi(2);
+    let m = (357.529_11 + 35_999.050_29 * t - 0.000_153_7 * t.powi(2)).to_radians();
+    let e = 0.016_708_634 - 0.000_042_037 * t - 0.000_000_126_7 * t.powi(2);
+
+    // Equation of the centre (C)
+    let c = (1.914_602 - 0.004_817 * t - 0.000_014 * t.powi(2)) * m.sin()
+        + (0.019_993 - 0.000_101 * t) * (2.0 * m).sin()
+        + 0.000_289 * (3.0 * m).sin();
+
+    let true_anomaly = m + c.to_radians();
+    let distance_au = 1.000_001_018 * (1.0 - e * e) / (1.0 + e * true_anomaly.cos());
+
+    // Correct for nutation and aberration
+    let omega = (125.04 - 1934.136 * t).to_radians();
+    let longitude = (l0 + c - 0.005_69 - 0.004_78 * omega.sin()).rem_euclid(360.0);
+    let (right_ascension, declination) =
+        ecliptic_to_equatorial(longitude, 0.0, obliquity(t) + 0.002_56 * omega.cos());
+
+    SolarPosition {
+        longitude,
+        distance_au,
+        right_ascension,
+        declination,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solar_position_matches_meeus_example_25a() {
+        // 1992 October 13 at 0h TD, delta T is negligible at this precision
+        let position = solar_position(2_448_908.5);
+
+        assert!((position.longitude - 199.90895).abs() < 0.01);
+        assert!((position.distance_au - 0.99766).abs() < 0.0001);
+        assert!((position.right_ascension - 198.38083).abs() < 0.01);
+        assert!((position.declination - -7.78507).abs() < 0.01);
+    }
+}
@@ -0,0 +1,145 @@
+use axum::{
+    body::{boxed, Full},
+    http::{header, HeaderValue, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Base URI for the `type` member of problem details, each error kind gets its own slug
+const PROBLEM_TYPE_BASE: &str = "https://api.whynotcats.com/errors/";
+
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    GatewayTimeout,
+    Elasticsearch(elasticsearch::Error),
+    /// A tide gauge station was given, official tide predictions aren't integrated yet
+    TideGaugeNotImplemented,
+    /// A failure on our side, the message is logged rather than returned
+    Internal(String),
+}
+
+/// An RFC 7807 problem details body
+#[derive(Serialize, Debug, Clone)]
+struct ProblemDetails {
+    #[serde(rename = "type")]
+    problem_type: String,
+    title: &'static str,
+    status: u16,
+    detail: String,
+    /// Path of the request that failed, filled in by `problem_instance`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instance: Option<String>,
+    /// Extension members specific to the problem type
+    #[serde(flatten)]
+    extensions: Map<String, Value>,
+}
+
+impl ApiError {
+    fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::GatewayTimeout => StatusCode::GATEWAY_TIMEOUT,
+            ApiError::Elasticsearch(_) => StatusCode::BAD_GATEWAY,
+            ApiError::TideGaugeNotImplemented => StatusCode::NOT_IMPLEMENTED,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn slug_and_title(&self) -> (&'static str, &'static str) {
+        match self {
+            ApiError::BadRequest(_) => ("bad-request", "Bad Request"),
+            ApiError::GatewayTimeout => ("search-timeout", "Search Timeout"),
+            ApiError::Elasticsearch(_) => ("search-unavailable", "Search Unavailable"),
+            ApiError::TideGaugeNotImplemented => ("tide-gauge-unavailable", "Not Implemented"),
+            ApiError::Internal(_) => ("internal-error", "Internal Server Error"),
+        }
+    }
+
+    fn detail(&self) -> String {
+        match self {
+            ApiError::BadRequest(message) => message.clone(),
+            ApiError::GatewayTimeout => "the search backend took too long to respond".to_string(),
+            ApiError::Elasticsearch(err) => {
+                log::error!("elasticsearch error: {}", err);
+                "unable to reach the search backend".to_string()
+            }
+            ApiError::TideGaugeNotImplemented => {
+                "tide gauge integration not yet available".to_string()
+            }
+            ApiError::Internal(message) => {
+                log::error!("internal error: {}", message);
+                "the server was unable to complete the request".to_string()
+            }
+        }
+    }
+
+    fn extensions(&self) -> Map<String, Value> {
+        match self {
+            ApiError::TideGaugeNotImplemented => {
+                let mut extensions = Map::new();
+                extensions.insert("station_accepted".to_string(), Value::Bool(true));
+                extensions.insert("eta".to_string(), Value::from("future"));
+                extensions
+            }
+            _ => Map::new(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let (slug, title) = self.slug_and_title();
+        let problem = ProblemDetails {
+            problem_type: format!("{}{}", PROBLEM_TYPE_BASE, slug),
+            title,
+            status: status.as_u16(),
+            detail: self.detail(),
+            instance: None,
+            extensions: self.extensions(),
+        };
+
+        let mut response = (
+            status,
+            [(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/problem+json"),
+            )],
+            serde_json::to_string(&problem).unwrap(),
+        )
+            .into_response();
+        response.extensions_mut().insert(problem);
+        response
+    }
+}
+
+/// Sets the `instance` of problem details responses to the request's path,
+/// which isn't known where the `ApiError` is turned into a response
+pub async fn problem_instance<B>(request: Request<B>, next: Next<B>) -> Response {
+    let path = request.uri().path().to_string();
+    let mut response = next.run(request).await;
+
+    match response.extensions_mut().remove::<ProblemDetails>() {
+        Some(mut problem) => {
+            problem.instance = Some(path);
+            let (mut parts, _) = response.into_parts();
+            parts.headers.remove(header::CONTENT_LENGTH);
+            let body = serde_json::to_string(&problem).unwrap();
+            Response::from_parts(parts, boxed(Full::from(body)))
+        }
+        None => response,
+    }
+}
+
+impl From<elasticsearch::Error> for ApiError {
+    fn from(err: elasticsearch::Error) -> Self {
+        if err.is_timeout() {
+            ApiError::GatewayTimeout
+        } else {
+            ApiError::Elasticsearch(err)
+        }
+    }
+}
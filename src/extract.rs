@@ -0,0 +1,153 @@
+use crate::ApiError;
+use axum::{
+    async_trait,
+    body::{Bytes, HttpBody},
+    extract::{self, FromRequest, RequestParts},
+    http::{header, Method},
+    BoxError,
+};
+use serde::de::DeserializeOwned;
+
+/// Like `axum::Form`, but fields the target type doesn't know about are
+/// logged as warnings rather than silently dropped, so typos such as
+/// `number_of_day` show up in the logs. They're still accepted so older
+/// servers keep working with newer clients.
+pub struct LoggedForm<T>(pub T);
+
+#[async_trait]
+impl<T, B> FromRequest<B> for LoggedForm<T>
+where
+    T: DeserializeOwned,
+    B: HttpBody + Send,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        // GET and HEAD carry the form in the query string, like `axum::Form`
+        let input = if req.method() == Method::GET || req.method() == Method::HEAD {
+            Bytes::copy_from_slice(req.uri().query().unwrap_or_default().as_bytes())
+        } else {
+            if !is_form_content_type(req) {
+                return Err(ApiError::BadRequest(
+                    "form requests must have `Content-Type: application/x-www-form-urlencoded`"
+                        .to_string(),
+                ));
+            }
+            Bytes::from_request(req)
+                .await
+                .map_err(|err| ApiError::BadRequest(err.to_string()))?
+        };
+
+        let (value, unknown_fields) = parse_form(&input)?;
+        for field in unknown_fields {
+            log::warn!("ignoring unknown form field {}", field);
+        }
+
+        Ok(LoggedForm(value))
+    }
+}
+
+fn is_form_content_type<B>(req: &RequestParts<B>) -> bool {
+    req.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .and_then(|content_type| content_type.split(';').next())
+        .is_some_and(|mime| {
+            mime.trim()
+                .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+        })
+}
+
+/// Deserializes a urlencoded form, along with the fields `T` doesn't know about
+fn parse_form<T: DeserializeOwned>(input: &[u8]) -> Result<(T, Vec<String>), ApiError> {
+    let mut unknown_fields = Vec::new();
+    let deserializer = serde_urlencoded::Deserializer::new(form_urlencoded::parse(input));
+    let value =
+        serde_ignored::deserialize(deserializer, |path| unknown_fields.push(path.to_string()))
+            .map_err(|err| ApiError::BadRequest(err.to_string()))?;
+
+    Ok((value, unknown_fields))
+}
+
+/// `axum::extract::Query`, rejecting bad query strings with problem details
+pub struct Query<T>(pub T);
+
+#[async_trait]
+impl<T, B> FromRequest<B> for Query<T>
+where
+    T: DeserializeOwned,
+    B: Send,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let extract::Query(value) = extract::Query::from_request(req)
+            .await
+            .map_err(|err| ApiError::BadRequest(err.to_string()))?;
+
+        Ok(Query(value))
+    }
+}
+
+/// `axum::Json` as an extractor, rejecting bad bodies with problem details
+pub struct JsonRequest<T>(pub T);
+
+#[async_trait]
+impl<T, B> FromRequest<B> for JsonRequest<T>
+where
+    T: DeserializeOwned,
+    B: HttpBody + Send,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let axum::Json(value) = axum::Json::from_request(req)
+            .await
+            .map_err(|err| ApiError::BadRequest(err.to_string()))?;
+
+        Ok(JsonRequest(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CreateCalendar;
+    use axum::{body::Body, http::Request};
+
+    fn post(content_type: &str, body: &'static str) -> RequestParts<Body> {
+        RequestParts::new(
+            Request::post("/ical")
+                .header(header::CONTENT_TYPE, content_type)
+                .body(Body::from(body))
+                .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn unknown_fields_are_reported_and_accepted() {
+        let form = "lat=51.5&lon=-0.1&number_of_day=3";
+        let (calendar, unknown_fields) = parse_form::<CreateCalendar>(form.as_bytes()).unwrap();
+        assert_eq!(unknown_fields, ["number_of_day"]);
+        assert_eq!(calendar.lat, 51.5);
+
+        let mut req = post("application/x-www-form-urlencoded; charset=utf-8", form);
+        let LoggedForm(calendar) = LoggedForm::<CreateCalendar>::from_request(&mut req)
+            .await
+            .unwrap();
+        assert_eq!(calendar.lon, -0.1);
+    }
+
+    #[tokio::test]
+    async fn posts_must_be_urlencoded() {
+        let mut req = post("application/json", "lat=51.5&lon=-0.1");
+        assert!(matches!(
+            LoggedForm::<CreateCalendar>::from_request(&mut req).await,
+            Err(ApiError::BadRequest(_))
+        ));
+    }
+}
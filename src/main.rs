@@ -1,29 +1,60 @@
+pub mod astro;
+pub mod error;
+pub mod extract;
 pub mod models;
+pub mod schema;
+pub mod statistics;
+pub mod telemetry;
 
+use astro::{
+    julian_to_unix,
+    lunar_position::lunar_position,
+    moonrise::{Approximate, MoonriseCalculator},
+    photography_score::{self, photography_score, MoonriseConditions, PhotographyWeights},
+    refraction::RefractionModel,
+    solar_position::solar_position,
+    unix_to_julian,
+};
 use axum::{
-    extract::{Extension, Query},
-    http::{header, HeaderMap, HeaderValue},
-    response::IntoResponse,
+    extract::{Extension, MatchedPath, RawQuery},
+    http::{header, HeaderMap, HeaderValue, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
     routing::get,
     routing::post,
-    Form, Router,
+    Json, Router,
 };
 use chrono::{prelude::*, Duration};
 use chrono_tz::Tz;
 use clap::Parser;
-use elasticsearch::{http::transport::Transport, Elasticsearch, SearchParts};
-use geodate::moon_transit::get_moonrise;
+use elasticsearch::{
+    http::request::JsonBody, ClearScrollParts, Elasticsearch, IndexParts, MsearchParts,
+    ScrollParts, SearchParts,
+};
+use geodate::{
+    earth_orbit::{
+        get_next_december_solstice, get_next_june_solstice, get_next_march_equinox,
+        get_next_september_equinox,
+    },
+    sun_transit::{get_sunrise, get_sunset},
+};
 use icalendar::Component;
 use serde_json::{json, Value};
 use std::{
-    error::Error,
+    collections::BTreeMap,
+    str::FromStr,
+    sync::{atomic::Ordering, Arc},
+};
+use std::{
+    future::Future,
     net::{IpAddr, Ipv6Addr, SocketAddr},
 };
-use std::{str::FromStr, sync::Arc};
 use tower::ServiceBuilder;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 
+pub use error::ApiError;
+use extract::{JsonRequest, LoggedForm, Query};
 pub use models::*;
 
 // Setup the command line interface with clap.
@@ -42,6 +73,30 @@ struct Opt {
     )]
     es: String,
 
+    /// set the timeout for elasticsearch requests
+    #[clap(long = "es-timeout-seconds", default_value = "30")]
+    es_timeout_seconds: u64,
+
+    /// set the minimum relevance score for location search results
+    #[clap(long = "es-min-score")]
+    es_min_score: Option<f64>,
+
+    /// set how long an idle location export scroll is kept open
+    #[clap(long = "scroll-timeout-seconds", default_value = "300")]
+    scroll_timeout_seconds: u64,
+
+    /// expose calculation traces to clients that ask for them
+    #[clap(long = "debug")]
+    debug: bool,
+
+    /// let location searches ask ES to explain their scoring, which is expensive
+    #[clap(long = "allow-explain")]
+    allow_explain: bool,
+
+    /// set the URL clients reach this server at
+    #[clap(long = "public-base-url", default_value = "https://api.whynotcats.com")]
+    public_base_url: String,
+
     /// set the listen addr
     #[clap(short = 'a', long = "addr", default_value = "::1")]
     addr: String,
@@ -60,15 +115,51 @@ async fn main() {
         std::env::set_var("RUST_LOG", format!("{},hyper=info,mio=info", opt.log_level))
     }
 
-    // enable console logging
-    tracing_subscriber::fmt::init();
+    // enable console logging, and span export when configured
+    let _telemetry = telemetry::init();
 
-    let shared_state = Arc::new(DBConnections { es: opt.es });
+    let shared_state = Arc::new(DBConnections {
+        es: opt.es,
+        es_timeout: std::time::Duration::from_secs(opt.es_timeout_seconds),
+        es_min_score: opt.es_min_score,
+        scroll_timeout: std::time::Duration::from_secs(opt.scroll_timeout_seconds),
+        debug: opt.debug,
+        allow_explain: opt.allow_explain,
+        public_base_url: opt.public_base_url,
+        metrics: Metrics::default(),
+        monthly_means: MonthlyMeanCache::default(),
+    });
 
     let app = Router::new()
-        .route("/ical", post(generate_calendar))
+        // GET so calendar apps can subscribe, see /moonrise/ical_webcal
+        .route("/ical", post(generate_calendar).get(generate_calendar))
+        .route("/moonrise/json", get(moonrise_json))
+        .route("/moonrise/azimuth", get(moonrise_azimuth))
+        .route("/moonrise/csv", get(moonrise_csv))
+        .route("/moonrise/tsv", get(moonrise_tsv))
+        .route("/moonrise/json_schema", get(moonrise_json_schema))
+        .route("/moonrise/ical_webcal", get(moonrise_webcal))
+        .route("/moonrise/ical_feed_metadata", get(ical_feed_metadata))
+        .route("/moonrise/ical_with_alerts", get(ical_with_alerts))
+        .route("/moonrise/ical_digest", get(moonrise_digest))
+        .route("/moonrise/statistics", get(moonrise_statistics))
+        .route("/moonrise/for_event", get(moonrise_for_event))
+        .route("/moonrise/latitude_sensitivity", get(latitude_sensitivity))
+        .route("/moonrise/notable_nights", get(notable_nights))
+        .route("/moonrise/opposition", get(moonrise_opposition))
+        .route("/moonrise/equinox_comparison", get(equinox_comparison))
+        .route(
+            "/moonrise/equatorial_mount_data",
+            get(equatorial_mount_data),
+        )
         .route("/search_location", get(search_locations))
+        .route("/search_location/bulk", post(bulk_search_locations))
+        .route("/search_location/scroll", get(scroll_locations))
         .route("/robots.txt", get(robots))
+        // Operational, so deliberately left out of the public documentation
+        .route("/api/status", get(server_status))
+        .layer(middleware::from_fn(count_requests))
+        .layer(middleware::from_fn(error::problem_instance))
         .layer(Extension(shared_state))
         .layer(CorsLayer::new().allow_origin(Any))
         .layer(ServiceBuilder::new().layer(TraceLayer::new_for_http()));
@@ -86,73 +177,1187 @@ async fn main() {
         .expect("Unable to start server");
 }
 
-async fn generate_calendar(Form(payload): Form<CreateCalendar>) -> impl IntoResponse {
+/// Largest manual moonrise correction accepted, either way, in seconds
+const MAX_HORIZON_CORRECTION_SECONDS: i64 = 3600;
+
+/// Longest reminder or follow-up event accepted, a day
+const MAX_WARNING_MINUTES: u64 = 1440;
+
+/// Sender of the calendar's events, RFC 5546 needs one for every METHOD
+const CALENDAR_ORGANIZER: &str = "mailto:moonrise@whynotcats.com";
+
+/// The same moonrise gets the same UID on every request, so a later
+/// `REQUEST` updates, and a `CANCEL` removes, an event sent before
+fn moonrise_uid(kind: &str, moonrise: i64, lat: f64, lon: f64) -> String {
+    format!("{}-{}-{:.4}-{:.4}@whynotcats.com", kind, moonrise, lat, lon)
+}
+
+/// The `mailto:` URI of the attendee a `REQUEST` or `CANCEL` is sent to,
+/// which both need, `None` for `PUBLISH`
+fn scheduling_attendee(
+    method: IcsMethod,
+    attendee: Option<&str>,
+) -> Result<Option<String>, ApiError> {
+    if method == IcsMethod::Publish {
+        return Ok(None);
+    }
+
+    let attendee = attendee.ok_or_else(|| {
+        ApiError::BadRequest(format!("ics_method {} needs an attendee", method.as_str()))
+    })?;
+    // Anything that could break out of the property value is refused
+    let is_address = match attendee.split_once('@') {
+        Some((user, domain)) => !user.is_empty() && domain.contains('.') && !domain.contains('@'),
+        None => false,
+    } && !attendee
+        .chars()
+        .any(|c| c.is_whitespace() || c.is_control() || matches!(c, ':' | ';' | ',' | '"'));
+    if !is_address {
+        return Err(ApiError::BadRequest(format!(
+            "{:?} is not an email address",
+            attendee
+        )));
+    }
+
+    Ok(Some(format!("mailto:{}", attendee)))
+}
+
+/// Marks `event` as part of the calendar's scheduling `method`, sent to `attendee`
+fn schedule(event: &mut icalendar::Event, method: IcsMethod, uid: &str, attendee: Option<&str>) {
+    event
+        .uid(uid)
+        .append_property(
+            icalendar::Property::new("ORGANIZER", CALENDAR_ORGANIZER)
+                .add_parameter("CN", "whynotcats")
+                .done(),
+        )
+        .add_property("SEQUENCE", &method.sequence().to_string());
+    if let Some(attendee) = attendee {
+        event.append_multi_property(
+            icalendar::Property::new("ATTENDEE", attendee)
+                .add_parameter("ROLE", "REQ-PARTICIPANT")
+                .add_parameter("RSVP", "FALSE")
+                .done(),
+        );
+    }
+    if method == IcsMethod::Cancel {
+        event.status(icalendar::EventStatus::Cancelled);
+    }
+}
+
+async fn generate_calendar(
+    LoggedForm(payload): LoggedForm<CreateCalendar>,
+    Extension(state): Extension<Arc<DBConnections>>,
+) -> Result<impl IntoResponse, ApiError> {
     // add input validation
+    let tz = parse_timezone(payload.timezone.clone())?;
+    if payload.tide_gauge_station.is_some() {
+        return Err(ApiError::TideGaugeNotImplemented);
+    }
+    let correction = payload.horizon_depression_correction_seconds.unwrap_or(0);
+    if !(-MAX_HORIZON_CORRECTION_SECONDS..=MAX_HORIZON_CORRECTION_SECONDS).contains(&correction) {
+        return Err(ApiError::BadRequest(format!(
+            "horizon_depression_correction_seconds must be between -{0} and {0}",
+            MAX_HORIZON_CORRECTION_SECONDS
+        )));
+    }
+    for (name, minutes) in [
+        ("before_warning_minutes", payload.before_warning_minutes),
+        ("after_warning_minutes", payload.after_warning_minutes),
+    ] {
+        if minutes.is_some_and(|minutes| minutes > MAX_WARNING_MINUTES) {
+            return Err(ApiError::BadRequest(format!(
+                "{} must be at most {}",
+                name, MAX_WARNING_MINUTES
+            )));
+        }
+    }
+    let started = std::time::Instant::now();
+    // Calculation traces are only exposed when the server runs with --debug
+    let mut trace =
+        (state.debug && payload.debug_mode.unwrap_or(false)).then(MoonriseTrace::default);
     let mut calendar = icalendar::Calendar::new();
-    let moonrises = generate_moonrises(payload.lat, payload.lon, payload.number_of_days);
+    let method = payload.ics_method.unwrap_or_default();
+    let attendee = scheduling_attendee(method, payload.attendee.as_deref())?;
+    calendar.append_property(icalendar::Property::new("METHOD", method.as_str()));
+    let moonrises = generate_moonrises_traced(
+        Utc::now(),
+        payload.lat,
+        payload.lon,
+        payload.number_of_days,
+        payload.use_ephemeris.unwrap_or_default().calculator(),
+        payload.refraction_model.unwrap_or_default(),
+        trace.as_mut(),
+    )
+    .into_iter()
+    .map(|moonrise| moonrise + correction);
 
-    let tz: Tz = payload
-        .clone()
-        .timezone
-        .unwrap_or_else(|| "UTC".to_string())
-        .parse()
-        .unwrap();
+    let geo = format!("{};{}", payload.lat, payload.lon);
+    let location = format!("{}, {}", payload.lat, payload.lon);
+    let transparency = if payload.block_time.unwrap_or(false) {
+        "OPAQUE"
+    } else {
+        "TRANSPARENT"
+    };
     for moonrise in moonrises {
-        let moonrise_date =
-            DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp_opt(moonrise, 0).unwrap(), Utc);
+        // Identified by the calculated moonrise, so changing the correction updates it
+        let uid = |kind| moonrise_uid(kind, moonrise - correction, payload.lat, payload.lon);
+        let moonrise_date = Utc.timestamp_opt(moonrise, 0).unwrap();
         let start = moonrise_date - Duration::minutes(payload.before as i64);
         let end = moonrise_date + Duration::minutes(payload.after as i64);
 
-        let event = icalendar::Event::new()
-            .summary(
-                &payload
-                    .clone()
-                    .summary
-                    .unwrap_or_else(|| "Moonrise".to_string()),
-            )
-            .description(format!("Moonrise @ {}", moonrise_date.with_timezone(&tz)).as_str())
+        let mut description = format!("Moonrise @ {}", moonrise_date.with_timezone(&tz));
+        if payload.include_distance_category.unwrap_or(false) {
+            let position = lunar_position(unix_to_julian(moonrise));
+            description.push_str(&format!(
+                ", moon in {}",
+                astro::distance_category(position.distance_km).replace('_', " ")
+            ));
+        }
+
+        if payload.include_tide_commentary.unwrap_or(false) {
+            // The dominant M2 tide peaks roughly as the Moon crosses the meridian,
+            // which is about half an M2 cycle after it rises
+            let hours = astro::M2_TIDAL_PERIOD_HOURS / 2.0;
+            let high_tide = moonrise_date + Duration::seconds((hours * 3600.0) as i64);
+            description.push_str(&format!(
+                "\nHigh tide approximately {:.1} hours after moonrise, around {} \
+                 (estimated, varies by location). Local geography significantly affects \
+                 actual tide times, this is not a tidal forecast.",
+                hours,
+                high_tide.with_timezone(&tz).format("%H:%M")
+            ));
+        }
+
+        if payload.include_cultural_names.unwrap_or(false) {
+            let full_moon = astro::nearest_full_moon(moonrise);
+            if (full_moon - moonrise).abs() <= astro::FULL_MOON_WINDOW_SECONDS {
+                let month = Utc.timestamp_opt(full_moon, 0).unwrap().with_timezone(&tz);
+                description.push_str(&format!(
+                    "\n{}: {}",
+                    month.format("%B"),
+                    astro::cultural_names::full_moon_name(
+                        payload.culture.unwrap_or_default(),
+                        month.month()
+                    )
+                ));
+            }
+        }
+
+        if payload.moon_sets_before_sunrise.unwrap_or(false) {
+            if let Some(note) = dark_sky_note(moonrise, payload.lat, payload.lon) {
+                description.push_str(&format!("\n{}", note));
+            }
+        }
+
+        let mut summary = payload
+            .summary
+            .clone()
+            .unwrap_or_else(|| "Moonrise".to_string());
+        if astro::eclipses::eclipse_at(moonrise).is_some() {
+            summary.push_str(" 🌑 Lunar Eclipse visible!");
+        }
+
+        let mut event = icalendar::Event::new()
+            .summary(&summary)
+            .description(&description)
             .starts(start)
             .ends(end)
+            .add_property("TRANSP", transparency)
+            .add_property("GEO", &geo)
+            .location(&location)
             .done();
+        schedule(&mut event, method, &uid("moonrise"), attendee.as_deref());
 
         calendar.push(event);
+
+        // Reminders are shown alongside the moonrise without blocking time
+        if let Some(minutes) = payload.before_warning_minutes {
+            let mut reminder = icalendar::Event::new()
+                .summary(&format!("🌕 Moonrise in {} minutes", minutes))
+                .starts(start - Duration::minutes(minutes as i64))
+                .ends(start)
+                .add_property("TRANSP", "TRANSPARENT")
+                .add_property("GEO", &geo)
+                .location(&location)
+                .done();
+            schedule(
+                &mut reminder,
+                method,
+                &uid("before-warning"),
+                attendee.as_deref(),
+            );
+            calendar.push(reminder);
+        }
+        if let Some(minutes) = payload.after_warning_minutes {
+            let mut follow_up = icalendar::Event::new()
+                .summary(&format!("🌕 Moonrise follow-up ({} minutes)", minutes))
+                .starts(end)
+                .ends(end + Duration::minutes(minutes as i64))
+                .add_property("TRANSP", "TRANSPARENT")
+                .add_property("GEO", &geo)
+                .location(&location)
+                .done();
+            schedule(
+                &mut follow_up,
+                method,
+                &uid("after-warning"),
+                attendee.as_deref(),
+            );
+            calendar.push(follow_up);
+        }
     }
 
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        header::CONTENT_TYPE,
-        HeaderValue::from_static("application/octet-stream; charset=utf-8"),
+    let mut content = calendar.to_string();
+    if payload.utf8_bom.unwrap_or(false) {
+        content.insert(0, '\u{FEFF}');
+    }
+    let mut headers = calendar_headers("moonrises.ical");
+    if let Some(trace) = trace {
+        let debug_headers = [
+            ("x-debug-julian-dates", format!("{:?}", trace.julian_dates)),
+            (
+                "x-debug-raw-moonrises",
+                format!("{:?}", trace.raw_moonrises),
+            ),
+            (
+                "x-debug-calculation-ms",
+                started.elapsed().as_millis().to_string(),
+            ),
+        ];
+        for (name, value) in debug_headers {
+            headers.insert(name, HeaderValue::from_str(&value).unwrap());
+        }
+    }
+
+    Ok((headers, content))
+}
+
+async fn moonrise_digest(Query(query): Query<DigestQuery>) -> Result<impl IntoResponse, ApiError> {
+    let tz = parse_timezone(query.timezone)?;
+    let week = Utc::now()
+        .with_timezone(&tz)
+        .date_naive()
+        .week(query.week_start_day.unwrap_or(Weekday::Mon));
+    let (first_day, last_day) = (week.first_day(), week.last_day());
+
+    let start = local_instant(&tz, first_day, NaiveTime::MIN)?.with_timezone(&Utc);
+    // Pad either side so moonrises close to midnight at the week boundaries are kept
+    let moonrises: Vec<DateTime<Tz>> =
+        generate_moonrises_from(start - Duration::days(1), query.lat, query.lon, 10)
+            .into_iter()
+            .map(|moonrise| Utc.timestamp_opt(moonrise, 0).unwrap().with_timezone(&tz))
+            .filter(|moonrise| (first_day..=last_day).contains(&moonrise.date_naive()))
+            .collect();
+
+    // Full moons are a lunation apart, so only the one nearest mid-week can fall in it
+    let middle_of_week = start + Duration::hours(84);
+    let full_moon = Utc
+        .timestamp_opt(astro::nearest_full_moon(middle_of_week.timestamp()), 0)
+        .single()
+        .map(|full_moon| full_moon.with_timezone(&tz))
+        .filter(|full_moon| (first_day..=last_day).contains(&full_moon.date_naive()));
+
+    let mut summary = format!(
+        "Week of {}: {} moonrises",
+        first_day.format("%B %-d"),
+        moonrises.len()
+    );
+    if let Some(full_moon) = full_moon {
+        summary.push_str(&format!(", Full Moon {}", full_moon.format("%B %-d")));
+    }
+
+    let mut calendar = icalendar::Calendar::new();
+    // The digest is regenerated weekly
+    calendar.append_property(icalendar::Property::new("X-PUBLISHED-TTL", "P7D"));
+    calendar.push(
+        icalendar::Event::new()
+            .summary(&summary)
+            .starts(first_day)
+            .ends(last_day + Duration::days(1))
+            .add_property("TRANSP", "TRANSPARENT")
+            .done(),
     );
+    for moonrise in moonrises {
+        calendar.push(
+            icalendar::Event::new()
+                .summary("Moonrise")
+                .description(&moonrise.format("%a %H:%M").to_string())
+                .starts(moonrise.with_timezone(&Utc))
+                .ends(moonrise.with_timezone(&Utc))
+                .done(),
+        );
+    }
+
+    Ok((
+        calendar_headers("moonrise_digest.ical"),
+        calendar.to_string(),
+    ))
+}
+
+/// Redirects to the `webcal://` subscription URL of the calendar with the same query
+async fn moonrise_webcal(
+    RawQuery(query): RawQuery,
+    Extension(state): Extension<Arc<DBConnections>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let base = state.public_base_url.trim_end_matches('/');
+    let host = base
+        .strip_prefix("https://")
+        .or_else(|| base.strip_prefix("http://"))
+        .unwrap_or(base);
+    let location = format!("webcal://{}/ical?{}", host, query.unwrap_or_default());
+    let location = HeaderValue::from_str(&location)
+        .map_err(|_| ApiError::BadRequest("invalid calendar query".to_string()))?;
+
+    Ok((
+        StatusCode::MOVED_PERMANENTLY,
+        [(header::LOCATION, location)],
+    ))
+}
+
+async fn ical_feed_metadata(
+    Query(query): Query<FeedMetadataQuery>,
+    Extension(state): Extension<Arc<DBConnections>>,
+) -> Result<Json<FeedMetadata>, ApiError> {
+    let tz = parse_timezone(query.timezone)?;
+
+    // The name is only a nicety, so the metadata is still returned without it
+    let resolved_location = match state.es_client() {
+        Ok(client) => get_nearest_location(client, query.lat, query.lon)
+            .await
+            .map_err(|err| log::warn!("reverse geocoding failed: {}", err))
+            .ok()
+            .flatten()
+            .map(|location| format!("{}, {}", location.name, location.country_code)),
+        Err(_) => None,
+    };
+
+    let now = Utc::now().timestamp();
+    let next_moonrise = generate_moonrises(query.lat, query.lon, 3)
+        .into_iter()
+        .find(|moonrise| *moonrise >= now)
+        .map(|moonrise| {
+            Utc.timestamp_opt(moonrise, 0)
+                .unwrap()
+                .with_timezone(&tz)
+                .to_rfc3339()
+        });
+
+    Ok(Json(FeedMetadata {
+        lat: query.lat,
+        lon: query.lon,
+        resolved_location,
+        timezone: tz.name().to_string(),
+        number_of_days: query.number_of_days,
+        next_moonrise,
+    }))
+}
+
+/// How long a queued SMS subscription is kept, in days
+const SMS_SUBSCRIPTION_TTL_DAYS: i64 = 90;
+
+/// Queues an SMS alert subscription for when SMS delivery exists
+async fn ical_with_alerts(
+    Query(query): Query<SmsAlertQuery>,
+    Extension(state): Extension<Arc<DBConnections>>,
+) -> Result<impl IntoResponse, ApiError> {
+    state
+        .metrics
+        .sms_subscription_requests_total
+        .fetch_add(1, Ordering::Relaxed);
+    if !is_e164(&query.phone) {
+        return Err(ApiError::BadRequest(format!(
+            "{:?} is not an E.164 phone number",
+            query.phone
+        )));
+    }
+
+    // ES has no per-document TTL, expired subscriptions are found by `expires_at`
+    let now = Utc::now();
+    let response = state
+        .es_client()?
+        .index(IndexParts::Index("sms_subscriptions"))
+        .body(json!({
+            "phone": query.phone,
+            "location": [query.lon, query.lat],
+            "number_of_days": query.number_of_days,
+            "created_at": now.to_rfc3339(),
+            "expires_at": (now + Duration::days(SMS_SUBSCRIPTION_TTL_DAYS)).to_rfc3339(),
+        }))
+        .send()
+        .await?
+        .error_for_status_code()?;
+    let body = response.json::<Value>().await?;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(SmsSubscriptionAccepted {
+            message:
+                "SMS alerts not yet available, subscription queued for future notification feature",
+            subscription_id: body["_id"].as_str().unwrap_or_default().to_string(),
+        }),
+    ))
+}
+
+/// A `+`, a country code and subscriber number of at most 15 digits in total
+fn is_e164(phone: &str) -> bool {
+    match phone.strip_prefix('+') {
+        Some(digits) => {
+            (2..=15).contains(&digits.len())
+                && !digits.starts_with('0')
+                && digits.chars().all(|c| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+/// Describes how the night around a moonrise suits dark-sky observation,
+/// when the Moon sets before astronomical dawn
+fn dark_sky_note(moonrise: i64, lat: f64, lon: f64) -> Option<String> {
+    let rise = unix_to_julian(moonrise);
+    // Start a little after the rise so the Moon is clear of the horizon
+    let set = astro::next_moonset(rise + 10.0 / 1440.0, lat, lon)?;
+    let dawn = astro::next_astronomical_dawn(rise, lat, lon)?;
+    if set >= dawn {
+        return None;
+    }
+
+    match astro::previous_astronomical_dusk(dawn, lat, lon) {
+        Some(dusk) if rise >= dusk => Some(format!(
+            "Full darkness moon window: {:.1} hours",
+            (set - rise) * 24.0
+        )),
+        _ => Some(
+            "Moon sets before dawn — excellent for dark-sky observation after moonset".to_string(),
+        ),
+    }
+}
+
+fn calendar_headers(filename: &str) -> HeaderMap {
+    attachment_headers("application/octet-stream; charset=utf-8", filename)
+}
+
+fn attachment_headers(content_type: &'static str, filename: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
     headers.insert(
         header::CONTENT_DISPOSITION,
-        HeaderValue::from_static("attachment; filename=\"moonrises.ical\""),
+        HeaderValue::from_str(&format!("attachment; filename=\"{}\"", filename)).unwrap(),
+    );
+
+    headers
+}
+
+async fn moonrise_json(
+    Query(query): Query<MoonriseQuery>,
+    Extension(state): Extension<Arc<DBConnections>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let tz = parse_timezone(query.timezone)?;
+    let today = Utc::now().with_timezone(&tz).date_naive();
+    let astronomical_notation = query.include_astronomical_notation.unwrap_or(false);
+    let azimuth_format = query.azimuth_format.unwrap_or_default();
+    let astronomical_data = query.include_astronomical_data.unwrap_or(false);
+    let illumination_data = query.include_illumination_data.unwrap_or(false);
+    let landmark = query.landmark_lat.zip(query.landmark_lon);
+    let magnetic_declination = query.include_magnetic_declination.unwrap_or(false);
+    let emoji_resolution = query
+        .include_moon_emoji
+        .unwrap_or(false)
+        .then(|| query.emoji_resolution.unwrap_or_default());
+    let round_degrees = |degrees: f64| (degrees * 10.0).round() / 10.0;
+    // 5 decimal places of a day is about a second
+    let round_day = |days: f64| (days * 1e5).round() / 1e5;
+
+    let events = generate_moonrises_traced(
+        Utc::now(),
+        query.lat,
+        query.lon,
+        query.number_of_days,
+        query.use_ephemeris.unwrap_or_default().calculator(),
+        RefractionModel::default(),
+        None,
+    )
+    .into_iter()
+    .map(|moonrise| {
+        let local = Utc.timestamp_opt(moonrise, 0).unwrap().with_timezone(&tz);
+        let jd = unix_to_julian(moonrise);
+        let position = lunar_position(jd);
+        let monthly_mean = monthly_mean_moonrise(&state, query.lat, query.lon, &tz, &local);
+        let azimuth = astro::azimuth(
+            jd,
+            query.lat,
+            query.lon,
+            position.right_ascension,
+            position.declination,
+        );
+        let magnetic_declination = magnetic_declination
+            .then(|| astro::magnetic::magnetic_declination(jd, query.lat, query.lon))
+            .flatten();
+        let meridian_altitude = astro::next_upper_transit(jd, query.lon).map(|transit| {
+            astro::meridian_altitude(query.lat, lunar_position(transit).declination)
+        });
+        MoonriseEvent {
+            timestamp: moonrise,
+            moonrise: local.to_rfc3339(),
+            is_tonight: local.date_naive() == today,
+            moon_distance_category: astro::distance_category(position.distance_km).to_string(),
+            sun_moon_angle_degrees: (astro::sun_moon_angle(jd) * 10.0).round() / 10.0,
+            azimuth: azimuth_format.format(azimuth),
+            delta_from_monthly_mean_seconds: monthly_mean
+                .map(|mean| statistics::seconds_from_mean(&local, mean)),
+            julian_date: astronomical_notation.then(|| round_day(unix_to_julian(moonrise))),
+            modified_julian_date: astronomical_notation
+                .then(|| round_day(astro::unix_to_mjd(moonrise))),
+            hour_angle_at_rise_degrees: astronomical_data.then(|| {
+                (astro::hour_angle(jd, query.lon, position.right_ascension) * 100.0).round() / 100.0
+            }),
+            illuminance_millilux: illumination_data
+                .then(|| (astro::moon_illuminance_lux(jd) * 1e4).round() / 10.0),
+            bearing_to_landmark: landmark.map(|(lat, lon)| {
+                astro::round_bearing(astro::initial_bearing(query.lat, query.lon, lat, lon))
+            }),
+            // Seen from the landmark, in the landmark's own frame of north
+            bearing_from_landmark_to_moonrise: landmark.map(|(lat, lon)| {
+                astro::round_bearing(astro::azimuth(
+                    jd,
+                    lat,
+                    lon,
+                    position.right_ascension,
+                    position.declination,
+                ))
+            }),
+            moon_phase_emoji: emoji_resolution
+                .map(|resolution| resolution.emoji(astro::lunar_phase_fraction(jd))),
+            moon_phase_index_29: (emoji_resolution == Some(EmojiResolution::Fine29))
+                .then(|| astro::phase_index(astro::lunar_phase_fraction(jd), 29) as u8),
+            magnetic_declination_degrees: magnetic_declination.map(round_degrees),
+            magnetic_azimuth_degrees: magnetic_declination
+                .map(|declination| astro::round_bearing(azimuth - declination)),
+            meridian_altitude_degrees: meridian_altitude.map(round_degrees),
+            low_transit_warning: meridian_altitude
+                .is_some_and(|altitude| altitude < astro::LOW_TRANSIT_ALTITUDE),
+        }
+    })
+    .collect();
+
+    Ok(Json(MoonriseList {
+        schema_version: schema::MOONRISE_SCHEMA_VERSION,
+        timezone: tz.name().to_string(),
+        events,
+    }))
+}
+
+async fn moonrise_csv(
+    Query(query): Query<MoonriseTableQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    Ok((
+        attachment_headers("text/csv; charset=utf-8", "moonrises.csv"),
+        moonrise_table(query, b',')?,
+    ))
+}
+
+async fn moonrise_tsv(
+    Query(query): Query<MoonriseTableQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    Ok((
+        attachment_headers("text/tab-separated-values; charset=utf-8", "moonrises.tsv"),
+        moonrise_table(query, b'\t')?,
+    ))
+}
+
+/// The moonrise export shared by the CSV and TSV endpoints, with fields separated by `delimiter`
+fn moonrise_table(query: MoonriseTableQuery, delimiter: u8) -> Result<String, ApiError> {
+    delimited(&moonrise_rows(query)?, delimiter)
+}
+
+fn moonrise_rows(query: MoonriseTableQuery) -> Result<Vec<MoonriseRow>, ApiError> {
+    let tz = parse_timezone(query.timezone)?;
+
+    Ok(
+        generate_moonrises(query.lat, query.lon, query.number_of_days)
+            .into_iter()
+            .map(|moonrise| {
+                let utc = Utc.timestamp_opt(moonrise, 0).unwrap();
+                let local = utc.with_timezone(&tz);
+                let jd = unix_to_julian(moonrise);
+                let (moon, sun) = (lunar_position(jd), solar_position(jd));
+                let illumination = astro::illuminated_fraction(astro::elongation(&moon, &sun));
+
+                MoonriseRow {
+                    date: local.date_naive().to_string(),
+                    moonrise_utc: utc.to_rfc3339(),
+                    moonrise_local: local.to_rfc3339(),
+                    timezone: tz.name().to_string(),
+                    julian_date: (jd * 1e5).round() / 1e5,
+                    illumination_pct: (illumination * 1000.0).round() / 10.0,
+                    phase_name: astro::phase_name(illumination, astro::is_waxing(&moon, &sun)),
+                }
+            })
+            .collect(),
+    )
+}
+
+/// RFC 4180 style rows with a header line, separated by `delimiter`
+fn delimited(rows: &[MoonriseRow], delimiter: u8) -> Result<String, ApiError> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .terminator(csv::Terminator::CRLF)
+        .from_writer(Vec::new());
+    for row in rows {
+        writer
+            .serialize(row)
+            .map_err(|err| ApiError::Internal(format!("unable to write moonrise row: {}", err)))?;
+    }
+    let table = writer
+        .into_inner()
+        .map_err(|err| ApiError::Internal(format!("unable to write moonrise table: {}", err)))?;
+
+    // Every field is a `String`, a float or a `&str`
+    Ok(String::from_utf8(table).unwrap())
+}
+
+async fn moonrise_azimuth(
+    Query(query): Query<AzimuthQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let tz = parse_timezone(query.timezone)?;
+    let azimuth_format = query.azimuth_format.unwrap_or_default();
+
+    let azimuths = generate_moonrises(query.lat, query.lon, query.number_of_days)
+        .into_iter()
+        .map(|moonrise| MoonriseAzimuth {
+            moonrise: Utc
+                .timestamp_opt(moonrise, 0)
+                .unwrap()
+                .with_timezone(&tz)
+                .to_rfc3339(),
+            azimuth: azimuth_format
+                .format(moonrise_azimuth_degrees(moonrise, query.lat, query.lon)),
+        })
+        .collect();
+
+    Ok(Json(AzimuthList {
+        timezone: tz.name().to_string(),
+        azimuths,
+    }))
+}
+
+/// Bearing of the Moon at the moment it rises, in degrees east of north
+fn moonrise_azimuth_degrees(moonrise: i64, lat: f64, lon: f64) -> f64 {
+    let jd = unix_to_julian(moonrise);
+    let moon = lunar_position(jd);
+
+    astro::azimuth(jd, lat, lon, moon.right_ascension, moon.declination)
+}
+
+async fn moonrise_json_schema() -> impl IntoResponse {
+    (
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/schema+json"),
+        )],
+        schema::MOONRISE_JSON_SCHEMA,
+    )
+}
+
+async fn moonrise_statistics(
+    Query(query): Query<StatisticsQuery>,
+) -> Result<Json<StatisticsResponse>, ApiError> {
+    let tz = parse_timezone(query.timezone)?;
+    let moonrises = moonrises_for_year(query.year, query.lat, query.lon, &tz)?;
+
+    let statistics = statistics::group_moonrises(
+        &moonrises,
+        query.group_by.unwrap_or_default(),
+        query.week_start_day.unwrap_or(Weekday::Mon),
+    );
+
+    Ok(Json(StatisticsResponse {
+        year: query.year,
+        timezone: tz.name().to_string(),
+        statistics,
+    }))
+}
+
+/// How many days either side of an event's date to look for a moonrise, about half a lunation
+const EVENT_SEARCH_DAYS: i64 = 15;
+
+async fn moonrise_for_event(
+    Query(query): Query<ForEventQuery>,
+) -> Result<Json<EventMoonrise>, ApiError> {
+    let tz = parse_timezone(query.timezone)?;
+    let date = query.event_date;
+    let midday = local_instant(&tz, date, NaiveTime::from_hms_opt(12, 0, 0).unwrap())?;
+
+    let moonrises = moonrises_between(
+        date - Duration::days(EVENT_SEARCH_DAYS),
+        date + Duration::days(EVENT_SEARCH_DAYS),
+        query.lat,
+        query.lon,
+        &tz,
+    )?;
+    let moonrise = moonrises
+        .iter()
+        .find(|moonrise| moonrise.date_naive() == date)
+        .or_else(|| {
+            moonrises
+                .iter()
+                .min_by_key(|moonrise| (**moonrise - midday).num_seconds().abs())
+        })
+        .ok_or_else(|| {
+            ApiError::BadRequest(format!(
+                "the Moon doesn't rise within {} days of {} here",
+                EVENT_SEARCH_DAYS, date
+            ))
+        })?;
+
+    let timestamp = moonrise.timestamp();
+    let jd = unix_to_julian(timestamp);
+    let conditions = MoonriseConditions::at(jd, query.lat, query.lon);
+    let score = photography_score(&conditions, &PhotographyWeights::default());
+
+    Ok(Json(EventMoonrise {
+        event_date: date,
+        moonrise: moonrise.to_rfc3339(),
+        moon_phase: astro::phase_name(conditions.illumination, conditions.waxing),
+        illumination_pct: (conditions.illumination * 1000.0).round() / 10.0,
+        moon_visible_duration_hours: astro::next_moonset(jd, query.lat, query.lon)
+            .map(|moonset| ((moonset - jd) * 240.0).round() / 10.0),
+        is_full_moon_night: (astro::nearest_full_moon(timestamp) - timestamp).abs()
+            <= astro::FULL_MOON_WINDOW_SECONDS,
+        quality_rating: photography_score::rating(score),
+    }))
+}
+
+/// How many nights `/moonrise/notable_nights` returns
+const NOTABLE_NIGHTS: usize = 10;
+
+async fn latitude_sensitivity(
+    Query(query): Query<LatitudeSensitivityQuery>,
+) -> Result<Json<Vec<LatitudeMoonrise>>, ApiError> {
+    let latitudes = query.lat_range_start..=query.lat_range_end;
+    if latitudes.is_empty() || *latitudes.start() < -90 || *latitudes.end() > 90 {
+        return Err(ApiError::BadRequest(
+            "lat_range_start must be at most lat_range_end, both between -90 and 90".to_string(),
+        ));
+    }
+
+    let start = query.date.and_hms_opt(0, 0, 0).unwrap().and_utc();
+    let lon = query.lon;
+    let date = query.date;
+    // Each latitude is a separate moonrise search, run them side by side
+    let searches: Vec<_> = latitudes
+        .map(|lat| {
+            tokio::task::spawn_blocking(move || {
+                // Pad either side, the generator can start on the previous day
+                let moonrise =
+                    generate_moonrises_from(start - Duration::days(1), lat as f64, lon, 4)
+                        .into_iter()
+                        .find(|moonrise| {
+                            Utc.timestamp_opt(*moonrise, 0).unwrap().date_naive() == date
+                        });
+                let azimuth = moonrise.map(|moonrise| {
+                    let jd = unix_to_julian(moonrise);
+                    let position = lunar_position(jd);
+                    let azimuth = astro::azimuth(
+                        jd,
+                        lat as f64,
+                        lon,
+                        position.right_ascension,
+                        position.declination,
+                    );
+                    astro::round_bearing(azimuth)
+                });
+
+                LatitudeMoonrise {
+                    lat,
+                    moonrise_utc: moonrise
+                        .map(|moonrise| Utc.timestamp_opt(moonrise, 0).unwrap().to_rfc3339()),
+                    azimuth,
+                }
+            })
+        })
+        .collect();
+
+    let mut moonrises = Vec::with_capacity(searches.len());
+    for search in searches {
+        moonrises.push(
+            search
+                .await
+                .map_err(|err| ApiError::Internal(format!("moonrise search failed: {}", err)))?,
+        );
+    }
+
+    Ok(Json(moonrises))
+}
+
+async fn notable_nights(
+    Query(query): Query<NotableNightsQuery>,
+) -> Result<Json<NotableNightsResponse>, ApiError> {
+    let tz = parse_timezone(query.timezone)?;
+    let defaults = PhotographyWeights::default();
+    let weights = PhotographyWeights {
+        illumination: query.weight_illumination.unwrap_or(defaults.illumination),
+        azimuth: query.weight_azimuth.unwrap_or(defaults.azimuth),
+        twilight: query.weight_twilight.unwrap_or(defaults.twilight),
+        glare: query.weight_glare.unwrap_or(defaults.glare),
+    };
+    let all_weights = [
+        weights.illumination,
+        weights.azimuth,
+        weights.twilight,
+        weights.glare,
+    ];
+    if all_weights.iter().any(|weight| *weight < 0.0) || all_weights.iter().sum::<f64>() <= 0.0 {
+        return Err(ApiError::BadRequest(
+            "weights must not be negative and at least one must be positive".to_string(),
+        ));
+    }
+
+    let mut scored: Vec<(DateTime<Tz>, MoonriseConditions, f64)> =
+        moonrises_for_year(query.year, query.lat, query.lon, &tz)?
+            .into_iter()
+            .map(|moonrise| {
+                let conditions = MoonriseConditions::at(
+                    unix_to_julian(moonrise.timestamp()),
+                    query.lat,
+                    query.lon,
+                );
+                let score = photography_score(&conditions, &weights);
+                (moonrise, conditions, score)
+            })
+            .collect();
+    scored.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+    let nights = scored
+        .into_iter()
+        .take(NOTABLE_NIGHTS)
+        .map(|(moonrise, conditions, score)| {
+            let darkness = photography_score::darkness_window(
+                unix_to_julian(moonrise.timestamp()),
+                query.lat,
+                query.lon,
+            );
+            NotableNight {
+                moonrise: moonrise.to_rfc3339(),
+                photogenic_score: (score * 10.0).round() / 10.0,
+                explanation: photography_score::explain(score, &conditions, darkness),
+            }
+        })
+        .collect();
+
+    Ok(Json(NotableNightsResponse {
+        year: query.year,
+        timezone: tz.name().to_string(),
+        nights,
+    }))
+}
+
+/// How close to sunset, in minutes, a full moon has to rise to be listed as near opposition
+const OPPOSITION_WINDOW_MINUTES: i64 = 30;
+
+async fn moonrise_opposition(
+    Query(query): Query<OppositionQuery>,
+) -> Result<Json<OppositionResponse>, ApiError> {
+    let tz = parse_timezone(query.timezone)?;
+
+    // The moonrise closest to sunset for each full moon, keyed by the full moon
+    let mut closest: BTreeMap<i64, (DateTime<Tz>, i64)> = BTreeMap::new();
+    for moonrise in moonrises_for_year(query.year, query.lat, query.lon, &tz)? {
+        let timestamp = moonrise.timestamp();
+        let full_moon = astro::nearest_full_moon(timestamp);
+        if (full_moon - timestamp).abs() > astro::FULL_MOON_WINDOW_SECONDS {
+            continue;
+        }
+        let Some(sunset) = nearest_sunset(timestamp, query.lat, query.lon) else {
+            continue;
+        };
+        let offset = timestamp - sunset;
+        let entry = closest.entry(full_moon).or_insert((moonrise, offset));
+        if offset.abs() < entry.1.abs() {
+            *entry = (moonrise, offset);
+        }
+    }
+
+    let mut nights: Vec<OppositionNight> = closest
+        .into_values()
+        .filter(|(_, offset)| offset.abs() <= OPPOSITION_WINDOW_MINUTES * 60)
+        .map(|(moonrise, offset)| {
+            let jd = unix_to_julian(moonrise.timestamp());
+            let moon = lunar_position(jd);
+            let illumination =
+                astro::illuminated_fraction(astro::elongation(&moon, &solar_position(jd)));
+            OppositionNight {
+                date: moonrise.date_naive(),
+                moonrise_offset_from_sunset_minutes: (offset as f64 / 60.0).round() as i64,
+                illumination_pct: (illumination * 1000.0).round() / 10.0,
+                distance_km: moon.distance_km.round() as u32,
+            }
+        })
+        .collect();
+    // Best first, the closer to sunset the closer to opposition
+    nights.sort_by_key(|night| night.moonrise_offset_from_sunset_minutes.abs());
+
+    Ok(Json(OppositionResponse {
+        year: query.year,
+        timezone: tz.name().to_string(),
+        nights,
+    }))
+}
+
+/// The sunset closest to `timestamp`, if the Sun sets around then
+fn nearest_sunset(timestamp: i64, lat: f64, lon: f64) -> Option<i64> {
+    (-1..=1)
+        .filter_map(|day| get_sunset(timestamp + day * 86400, lon, lat))
+        .min_by_key(|sunset| (sunset - timestamp).abs())
+}
+
+async fn equinox_comparison(
+    Query(query): Query<EquinoxComparisonQuery>,
+) -> Result<Json<EquinoxComparison>, ApiError> {
+    let tz = parse_timezone(query.timezone)?;
+    let year = Utc::now().with_timezone(&tz).year();
+    let moonrises = moonrises_for_year(year, query.lat, query.lon, &tz)?;
+
+    let (earliest_moonrise, latest_moonrise) = if moonrises.is_empty() {
+        (None, None)
+    } else {
+        let times = statistics::time_statistics(&moonrises.iter().collect::<Vec<_>>());
+        (Some(times.earliest), Some(times.latest))
+    };
+
+    let year_start = moonrises
+        .first()
+        .map(|moonrise| moonrise.timestamp())
+        .unwrap_or_else(|| Utc::now().timestamp());
+    let moonrise_on = |event: i64| {
+        let date = Utc
+            .timestamp_opt(event, 0)
+            .unwrap()
+            .with_timezone(&tz)
+            .date_naive();
+        moonrises
+            .iter()
+            .find(|moonrise| moonrise.date_naive() == date)
+            .map(|moonrise| moonrise.to_rfc3339())
+    };
+    let march_equinox = moonrise_on(get_next_march_equinox(year_start));
+    let june_solstice = moonrise_on(get_next_june_solstice(year_start));
+    let september_equinox = moonrise_on(get_next_september_equinox(year_start));
+    let december_solstice = moonrise_on(get_next_december_solstice(year_start));
+
+    // Seasons are flipped in the southern hemisphere
+    let (spring, summer, autumn, winter) = if query.lat >= 0.0 {
+        (
+            march_equinox,
+            june_solstice,
+            september_equinox,
+            december_solstice,
+        )
+    } else {
+        (
+            september_equinox,
+            december_solstice,
+            march_equinox,
+            june_solstice,
+        )
+    };
+
+    Ok(Json(EquinoxComparison {
+        year,
+        timezone: tz.name().to_string(),
+        earliest_moonrise,
+        latest_moonrise,
+        spring_equinox_moonrise: spring,
+        summer_solstice_moonrise: summer,
+        autumn_equinox_moonrise: autumn,
+        winter_solstice_moonrise: winter,
+        major_lunar_standstill_azimuth: astro::rising_azimuth(
+            query.lat,
+            astro::MAJOR_STANDSTILL_DECLINATION,
+        ),
+    }))
+}
+
+async fn equatorial_mount_data(
+    Query(query): Query<EquatorialMountQuery>,
+) -> Json<EquatorialMountData> {
+    let date = query.date.unwrap_or_else(Utc::now);
+    let jd = unix_to_julian(date.timestamp());
+    let moon = lunar_position(jd);
+    let hour_angle = astro::hour_angle(jd, query.lon, moon.right_ascension);
+    let round = |value: f64| (value * 10.0).round() / 10.0;
+
+    Json(EquatorialMountData {
+        date: date.to_rfc3339(),
+        local_sidereal_time: astro::format_hours(astro::local_sidereal_time(jd, query.lon)),
+        polar_elevation: round(query.lat.abs()),
+        moon_ra: astro::format_hours(moon.right_ascension),
+        moon_dec: astro::format_degrees(moon.declination),
+        hour_angle: astro::format_hours(hour_angle),
+        parallactic_angle: round(astro::parallactic_angle(
+            query.lat,
+            hour_angle,
+            moon.declination,
+        )),
+    })
+}
+
+/// Mean local moonrise time of day, in seconds, for the month of `moonrise`
+/// over the year before, the year of and the year after it
+fn monthly_mean_moonrise(
+    state: &DBConnections,
+    lat: f64,
+    lon: f64,
+    tz: &Tz,
+    moonrise: &DateTime<Tz>,
+) -> Option<f64> {
+    let (year, month) = (moonrise.year(), moonrise.month());
+    let key = (
+        (lat * 1e4).round() as i64,
+        (lon * 1e4).round() as i64,
+        tz.name().to_string(),
+        year,
+        month,
     );
 
-    let content = calendar.to_string();
-    (headers, content)
+    state.monthly_means.get_or_insert_with(key, || {
+        // The mean is only a nicety, so it's left out if a month can't be searched
+        let moonrises: Vec<DateTime<Tz>> = (year - 1..=year + 1)
+            .map(|year| {
+                let first_day = NaiveDate::from_ymd_opt(year, month, 1).unwrap();
+                let last_day = first_day
+                    .checked_add_months(chrono::Months::new(1))
+                    .unwrap()
+                    .pred_opt()
+                    .unwrap();
+                moonrises_between(first_day, last_day, lat, lon, tz)
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?
+            .into_iter()
+            .flatten()
+            .collect();
+
+        (!moonrises.is_empty())
+            .then(|| statistics::mean_seconds_of_day(&moonrises.iter().collect::<Vec<_>>()))
+    })
 }
 
-fn unix_to_julian(timestamp: i64) -> f64 {
-    (timestamp as f64 / 86400.0) + 2440587.5
+/// All moonrises falling within the given calendar year in `tz`
+fn moonrises_for_year(
+    year: i32,
+    lat: f64,
+    lon: f64,
+    tz: &Tz,
+) -> Result<Vec<DateTime<Tz>>, ApiError> {
+    let first_day = NaiveDate::from_ymd_opt(year, 1, 1)
+        .ok_or_else(|| ApiError::BadRequest(format!("invalid year {}", year)))?;
+    let last_day = NaiveDate::from_ymd_opt(year, 12, 31).unwrap();
+
+    moonrises_between(first_day, last_day, lat, lon, tz)
 }
 
-fn julian_to_unix(jd: f64) -> i64 {
-    ((jd - 2440587.5) * 86400.0).round() as i64
+/// All moonrises falling on the days from `first_day` to `last_day` in `tz`
+fn moonrises_between(
+    first_day: NaiveDate,
+    last_day: NaiveDate,
+    lat: f64,
+    lon: f64,
+    tz: &Tz,
+) -> Result<Vec<DateTime<Tz>>, ApiError> {
+    // Pad a day either side so moonrises close to midnight at the boundaries are kept
+    let start =
+        local_instant(tz, first_day, NaiveTime::MIN)?.with_timezone(&Utc) - Duration::days(1);
+    let number_of_days = (last_day - first_day).num_days() as usize + 3;
+
+    Ok(generate_moonrises_from(start, lat, lon, number_of_days)
+        .into_iter()
+        .map(|moonrise| Utc.timestamp_opt(moonrise, 0).unwrap().with_timezone(tz))
+        .filter(|moonrise| (first_day..=last_day).contains(&moonrise.date_naive()))
+        .collect())
+}
+
+/// Clocks skip over whole quarter hours, when they skip at all
+const CLOCK_CHANGE_STEP_MINUTES: i64 = 15;
+
+/// `time` on `date` in `tz`, or the first instant after it that exists when
+/// the clocks skip over it, like midnight in Santiago when DST starts
+fn local_instant(tz: &Tz, date: NaiveDate, time: NaiveTime) -> Result<DateTime<Tz>, ApiError> {
+    let local = date.and_time(time);
+
+    (0..=24 * 60 / CLOCK_CHANGE_STEP_MINUTES)
+        .find_map(|step| {
+            tz.from_local_datetime(&(local + Duration::minutes(step * CLOCK_CHANGE_STEP_MINUTES)))
+                .earliest()
+        })
+        .ok_or_else(|| ApiError::BadRequest(format!("{} never happens in {}", local, tz.name())))
+}
+
+fn parse_timezone(timezone: Option<String>) -> Result<Tz, ApiError> {
+    let timezone = timezone.unwrap_or_else(|| "UTC".to_string());
+    timezone
+        .parse()
+        .map_err(|_| ApiError::BadRequest(format!("unknown timezone {}", timezone)))
 }
 
 fn generate_moonrises(lat: f64, lon: f64, number_of_days: usize) -> Vec<i64> {
-    let local: DateTime<Utc> = Utc::now();
+    generate_moonrises_from(Utc::now(), lat, lon, number_of_days)
+}
+
+fn generate_moonrises_from(
+    local: DateTime<Utc>,
+    lat: f64,
+    lon: f64,
+    number_of_days: usize,
+) -> Vec<i64> {
+    generate_moonrises_traced(
+        local,
+        lat,
+        lon,
+        number_of_days,
+        &Approximate,
+        RefractionModel::default(),
+        None,
+    )
+}
+
+/// How many of the first calculation steps are kept in a `MoonriseTrace`
+const TRACE_LENGTH: usize = 5;
+
+/// The first few intermediate values of a moonrise generation, for debugging
+#[derive(Default, Debug)]
+struct MoonriseTrace {
+    julian_dates: Vec<f64>,
+    raw_moonrises: Vec<i64>,
+}
+
+fn generate_moonrises_traced(
+    local: DateTime<Utc>,
+    lat: f64,
+    lon: f64,
+    number_of_days: usize,
+    calculator: &dyn MoonriseCalculator,
+    refraction: RefractionModel,
+    mut trace: Option<&mut MoonriseTrace>,
+) -> Vec<i64> {
     let mut moonrises = Vec::with_capacity(number_of_days);
     let mut previous_moonrise = 0;
     for i in 0..number_of_days {
         let l = local + Duration::days(i as i64);
         let jd = (unix_to_julian(l.timestamp()) + lon / 360.0 + 0.5).floor() - 0.5;
-        let mut next_moonrise = get_moonrise(julian_to_unix(jd), lon, lat);
+        let mut next_moonrise = calculator.moonrise(julian_to_unix(jd), lat, lon, refraction);
+
+        if let Some(trace) = trace.as_deref_mut() {
+            if trace.julian_dates.len() < TRACE_LENGTH {
+                trace.julian_dates.push(jd);
+                trace.raw_moonrises.extend(next_moonrise);
+            }
+        }
 
         // Check to see if there is an issue with generating moonrises too close to each other
         // This might have to do with daylight savings times, not sure
         if next_moonrise.is_some() && next_moonrise.unwrap() - previous_moonrise <= 500 {
-            next_moonrise = get_moonrise(julian_to_unix(jd + 1.), lon, lat);
+            next_moonrise = calculator.moonrise(julian_to_unix(jd + 1.), lat, lon, refraction);
         }
 
         if let Some(moonrise) = next_moonrise {
@@ -167,49 +1372,217 @@ fn generate_moonrises(lat: f64, lon: f64, number_of_days: usize) -> Vec<i64> {
     moonrises
 }
 
+/// The most queries accepted by a single bulk location search
+const MAX_BULK_QUERIES: usize = 50;
+
 // Really this is get population centers, until we can differentiate better on the data
+fn location_query(query: &str, filters: &LocationFilters) -> Value {
+    let mut body = json!({"query":
+    { "bool": {
+        "must": {
+            "multi_match": {
+                "fields": ["name", "country_code"],
+                "query": query,
+                "fuzziness": "AUTO"
+            }
+        },
+        "filter": [
+            { "range": { "population": { "gt": 0}}}
+        ] }
+    }});
+
+    if let Some(feature_codes) = &filters.feature_codes {
+        body["query"]["bool"]["filter"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({ "terms": { "feature_code": feature_codes } }));
+    }
+
+    if let Some(bbox) = filters.bounding_box {
+        body["query"]["bool"]["filter"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({ "geo_bounding_box": { "location": {
+                "top_left": { "lat": bbox.north, "lon": bbox.west },
+                "bottom_right": { "lat": bbox.south, "lon": bbox.east }
+            }}}));
+    }
+
+    if let Some(min_score) = filters.min_score {
+        body["min_score"] = json!(min_score);
+    }
+
+    if filters.explain {
+        body["explain"] = json!(true);
+    }
+
+    body
+}
+
+/// GeoNames feature codes are 2 to 5 uppercase letters or digits, e.g. `PPLC` or `ADM1`
+fn validate_feature_codes(feature_codes: Vec<String>) -> Result<Vec<String>, ApiError> {
+    feature_codes
+        .into_iter()
+        .map(|code| {
+            let code = code.trim().to_uppercase();
+            if (2..=5).contains(&code.len()) && code.chars().all(|c| c.is_ascii_alphanumeric()) {
+                Ok(code)
+            } else {
+                Err(ApiError::BadRequest(format!(
+                    "invalid feature code {:?}",
+                    code
+                )))
+            }
+        })
+        .collect()
+}
+
+fn locations_from_hits(body: &Value) -> Vec<LocationResponse> {
+    let mut data: Vec<LocationResponse> = Vec::new();
+    for hit in body["hits"]["hits"].as_array().unwrap() {
+        let mut location = LocationResponse::from_source_with_id(
+            hit["_id"].as_str().unwrap(),
+            hit["_source"].clone(),
+        );
+        location.score_explanation = hit.get("_explanation").cloned();
+        data.push(location);
+    }
+
+    data
+}
+
 async fn get_locations(
-    client: elasticsearch::Elasticsearch,
+    client: Elasticsearch,
     query: String,
-) -> Result<Vec<LocationResponse>, Box<dyn Error>> {
+    filters: LocationFilters,
+) -> Result<Vec<LocationResponse>, elasticsearch::Error> {
     let response = client
         .search(SearchParts::Index(&["geolocations"]))
-        .body(json!({"query":
-        { "bool": {
-            "must": {
-                "multi_match": {
-                    "fields": ["name", "country_code"],
-                    "query": query,
-                    "fuzziness": "AUTO"
-                }
-            },
-            "filter": {
-                "range": { "population": { "gt": 0}}
-            } }
-        }}))
+        .body(location_query(&query, &filters))
         .send()
-        .await?;
+        .await?
+        .error_for_status_code()?;
 
     let body = response.json::<Value>().await?;
-    let mut data: Vec<LocationResponse> = Vec::new();
-    for hit in body["hits"]["hits"].as_array().unwrap() {
-        data.push(LocationResponse::from_source_with_id(
-            hit["_id"].as_str().unwrap(),
-            hit["_source"].clone(),
-        ));
+
+    Ok(locations_from_hits(&body))
+}
+
+/// The populated place closest to `lat`/`lon`
+async fn get_nearest_location(
+    client: Elasticsearch,
+    lat: f64,
+    lon: f64,
+) -> Result<Option<LocationResponse>, elasticsearch::Error> {
+    let response = client
+        .search(SearchParts::Index(&["geolocations"]))
+        .body(json!({
+            "size": 1,
+            "query": { "bool": { "filter": [
+                { "range": { "population": { "gt": 0}}}
+            ] } },
+            "sort": [{ "_geo_distance": {
+                "location": { "lat": lat, "lon": lon },
+                "order": "asc"
+            }}]
+        }))
+        .send()
+        .await?
+        .error_for_status_code()?;
+
+    let body = response.json::<Value>().await?;
+
+    Ok(locations_from_hits(&body).into_iter().next())
+}
+
+async fn get_bulk_locations(
+    client: Elasticsearch,
+    queries: &[String],
+    limit_per_query: Option<usize>,
+    filters: LocationFilters,
+) -> Result<Vec<Value>, elasticsearch::Error> {
+    // Each search in a multi search is a header line followed by the query body
+    let mut body: Vec<JsonBody<Value>> = Vec::with_capacity(queries.len() * 2);
+    for query in queries {
+        let mut search = location_query(query, &filters);
+        if let Some(limit) = limit_per_query {
+            search["size"] = json!(limit);
+        }
+        body.push(json!({}).into());
+        body.push(search.into());
     }
 
-    Ok(data)
+    let response = client
+        .msearch(MsearchParts::Index(&["geolocations"]))
+        .body(body)
+        .send()
+        .await?
+        .error_for_status_code()?;
+    let mut response_body = response.json::<Value>().await?;
+
+    // Responses come back in the same order as the searches were sent
+    Ok(match response_body["responses"].take() {
+        Value::Array(responses) => responses,
+        _ => Vec::new(),
+    })
+}
+
+/// Bounds an ES request by a per-request timeout, on top of the transport timeout,
+/// so the shorter of the two wins
+async fn with_timeout<T>(
+    timeout_seconds: Option<u64>,
+    request: impl Future<Output = Result<T, elasticsearch::Error>>,
+) -> Result<T, ApiError> {
+    match timeout_seconds {
+        Some(seconds) => tokio::time::timeout(std::time::Duration::from_secs(seconds), request)
+            .await
+            .map_err(|_| ApiError::GatewayTimeout)?
+            .map_err(ApiError::from),
+        None => request.await.map_err(ApiError::from),
+    }
 }
 
 async fn search_locations(
     search_query: Query<SearchQuery>,
     Extension(state): Extension<Arc<DBConnections>>,
-) -> impl IntoResponse {
-    let query = search_query.0.query;
+) -> Result<impl IntoResponse, ApiError> {
+    let search_query = search_query.0;
+    let bounding_box = search_query.bounding_box()?;
+    let feature_codes = search_query
+        .feature_codes
+        .map(validate_feature_codes)
+        .transpose()?;
+    let explain = search_query.explain.unwrap_or(false);
+    if explain && !state.allow_explain {
+        return Err(ApiError::BadRequest(
+            "explain is not enabled on this server".to_string(),
+        ));
+    }
+    let filters = LocationFilters {
+        min_score: search_query.min_score.or(state.es_min_score),
+        feature_codes,
+        bounding_box,
+        explain,
+    };
 
-    let client = Elasticsearch::new(Transport::single_node(&state.es).unwrap());
-    let results = get_locations(client, query).await.unwrap();
+    let client = state.es_client()?;
+    let results = with_timeout(
+        search_query.timeout_seconds,
+        get_locations(client, search_query.query, filters),
+    )
+    .await?;
+    let include_solar_today = search_query.include_solar_today.unwrap_or(false);
+    let format = search_query.response_format.unwrap_or_default();
+    let include_coordinates = search_query.include_coordinates.unwrap_or(true);
+    let results: Vec<SearchResponseItem> = results
+        .into_iter()
+        .map(|mut location| {
+            if include_solar_today {
+                location.solar_today = Some(solar_today(&location));
+            }
+            SearchResponseItem::new(location, format, include_coordinates)
+        })
+        .collect();
 
     println!("{} results", results.len());
     let body = serde_json::to_string(&results).unwrap();
@@ -219,9 +1592,315 @@ async fn search_locations(
         header::HeaderValue::from_static("application/json"),
     );
 
-    (headers, body)
+    Ok((headers, body))
+}
+
+/// Hits returned per page of a location export scroll
+const SCROLL_PAGE_SIZE: usize = 1000;
+
+/// Starts exporting all matching locations when given a `query`, or returns
+/// the next page of the export when given its `scroll_id`
+async fn scroll_locations(
+    Query(scroll_query): Query<ScrollQuery>,
+    Extension(state): Extension<Arc<DBConnections>>,
+) -> Result<Json<ScrollPage>, ApiError> {
+    let client = state.es_client()?;
+    let keep_alive = format!("{}s", state.scroll_timeout.as_secs());
+    let body = match (scroll_query.scroll_id, scroll_query.query) {
+        (Some(scroll_id), _) => {
+            client
+                .scroll(ScrollParts::None)
+                .body(json!({ "scroll": keep_alive, "scroll_id": scroll_id }))
+                .send()
+                .await?
+                .error_for_status_code()?
+                .json::<Value>()
+                .await?
+        }
+        (None, Some(query)) => {
+            let filters = LocationFilters {
+                min_score: scroll_query.min_score.or(state.es_min_score),
+                feature_codes: scroll_query
+                    .feature_codes
+                    .map(validate_feature_codes)
+                    .transpose()?,
+                bounding_box: GeoBoundingBox::from_edges(
+                    scroll_query.bbox_north,
+                    scroll_query.bbox_south,
+                    scroll_query.bbox_east,
+                    scroll_query.bbox_west,
+                )?,
+                explain: false,
+            };
+            let mut body = location_query(&query, &filters);
+            body["size"] = json!(SCROLL_PAGE_SIZE);
+            client
+                .search(SearchParts::Index(&["geolocations"]))
+                .scroll(&keep_alive)
+                .body(body)
+                .send()
+                .await?
+                .error_for_status_code()?
+                .json::<Value>()
+                .await?
+        }
+        (None, None) => {
+            return Err(ApiError::BadRequest(
+                "either query or scroll_id is required".to_string(),
+            ))
+        }
+    };
+
+    let locations = locations_from_hits(&body);
+    let mut scroll_id = body["_scroll_id"].as_str().map(str::to_string);
+    // A short page is the last one, free the scroll context rather than
+    // leaving it open until it times out
+    if locations.len() < SCROLL_PAGE_SIZE {
+        if let Some(scroll_id) = scroll_id.take() {
+            client
+                .clear_scroll(ClearScrollParts::None)
+                .body(json!({ "scroll_id": [scroll_id] }))
+                .send()
+                .await?;
+        }
+    }
+
+    let format = scroll_query.response_format.unwrap_or_default();
+    let include_coordinates = scroll_query.include_coordinates.unwrap_or(true);
+    Ok(Json(ScrollPage {
+        scroll_id,
+        results: locations
+            .into_iter()
+            .map(|location| SearchResponseItem::new(location, format, include_coordinates))
+            .collect(),
+    }))
+}
+
+fn solar_today(location: &LocationResponse) -> SolarToday {
+    let tz: Tz = location.timezone.parse().unwrap_or(Tz::UTC);
+    let noon = Utc::now()
+        .date_naive()
+        .and_hms_opt(12, 0, 0)
+        .unwrap()
+        .and_utc()
+        .timestamp();
+    let sunrise = get_sunrise(noon, location.longitude, location.latitude);
+    let sunset = get_sunset(noon, location.longitude, location.latitude);
+    let local = |timestamp: i64| {
+        Utc.timestamp_opt(timestamp, 0)
+            .unwrap()
+            .with_timezone(&tz)
+            .to_rfc3339()
+    };
+
+    SolarToday {
+        sunrise: sunrise.map(local),
+        sunset: sunset.map(local),
+        day_length_seconds: sunrise
+            .zip(sunset)
+            .map(|(sunrise, sunset)| sunset - sunrise),
+    }
+}
+
+async fn bulk_search_locations(
+    JsonRequest(payload): JsonRequest<BulkSearchQuery>,
+    Extension(state): Extension<Arc<DBConnections>>,
+) -> Result<Json<BulkSearchResponse>, ApiError> {
+    if payload.queries.len() > MAX_BULK_QUERIES {
+        return Err(ApiError::BadRequest(format!(
+            "at most {} queries are allowed per bulk search",
+            MAX_BULK_QUERIES
+        )));
+    }
+
+    let mut results = BTreeMap::new();
+    let mut errors = BTreeMap::new();
+    if payload.queries.is_empty() {
+        return Ok(Json(BulkSearchResponse { results, errors }));
+    }
+
+    let client = state.es_client()?;
+    let responses = with_timeout(
+        payload.timeout_seconds,
+        get_bulk_locations(
+            client,
+            &payload.queries,
+            payload.limit_per_query,
+            LocationFilters {
+                min_score: state.es_min_score,
+                ..Default::default()
+            },
+        ),
+    )
+    .await?;
+
+    for (query, response) in payload.queries.into_iter().zip(responses) {
+        if let Some(error) = response.get("error") {
+            log::warn!("bulk search for {:?} failed: {}", query, error);
+            let reason = error["reason"]
+                .as_str()
+                .map_or_else(|| error.to_string(), str::to_string);
+            errors.insert(query, reason);
+        } else {
+            results.insert(query, locations_from_hits(&response));
+        }
+    }
+
+    Ok(Json(BulkSearchResponse { results, errors }))
+}
+
+/// Endpoint label for requests that match no route, such as 404s
+const UNMATCHED_ENDPOINT: &str = "unmatched";
+
+async fn count_requests<B>(request: Request<B>, next: Next<B>) -> Response {
+    if let Some(state) = request.extensions().get::<Arc<DBConnections>>() {
+        let endpoint = request
+            .extensions()
+            .get::<MatchedPath>()
+            .map_or(UNMATCHED_ENDPOINT, |path| path.as_str());
+        state.metrics.record_request(endpoint);
+    }
+
+    next.run(request).await
+}
+
+async fn server_status(Extension(state): Extension<Arc<DBConnections>>) -> Json<ServerStatus> {
+    let started = std::time::Instant::now();
+    let reachable = match state.es_client() {
+        Ok(client) => client
+            .ping()
+            .send()
+            .await
+            .map(|response| response.status_code().is_success())
+            .unwrap_or(false),
+        Err(_) => false,
+    };
+
+    Json(ServerStatus {
+        uptime_seconds: state.metrics.started_at.elapsed().as_secs(),
+        elasticsearch: ElasticsearchStatus {
+            reachable,
+            response_time_ms: started.elapsed().as_millis(),
+        },
+        requests: RequestCounts {
+            total: state.metrics.requests_total.load(Ordering::Relaxed),
+            by_endpoint: state.metrics.requests_by_endpoint(),
+        },
+        monthly_mean_cache: state.monthly_means.status(),
+        sms_subscription_requests_total: state
+            .metrics
+            .sms_subscription_requests_total
+            .load(Ordering::Relaxed),
+    })
 }
 
 async fn robots() -> &'static str {
     "User-Agent: *\nDisallow: /"
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(timezone: &str) -> MoonriseRow {
+        MoonriseRow {
+            date: "2024-03-01".to_string(),
+            moonrise_utc: "2024-03-01T23:15:00+00:00".to_string(),
+            moonrise_local: "2024-03-01T23:15:00+00:00".to_string(),
+            timezone: timezone.to_string(),
+            julian_date: 2_460_371.468_75,
+            illumination_pct: 62.5,
+            phase_name: "Waning Gibbous",
+        }
+    }
+
+    #[test]
+    fn csv_has_a_header_and_quotes_per_rfc_4180() {
+        let csv = delimited(&[row("UTC"), row("Odd, \"zone\"")], b',').unwrap();
+        let lines: Vec<_> = csv.split_terminator("\r\n").collect();
+
+        assert!(csv.ends_with("\r\n"));
+        assert_eq!(
+            lines,
+            [
+                "date,moonrise_utc,moonrise_local,timezone,julian_date,illumination_pct,phase_name",
+                "2024-03-01,2024-03-01T23:15:00+00:00,2024-03-01T23:15:00+00:00,UTC,2460371.46875,62.5,Waning Gibbous",
+                "2024-03-01,2024-03-01T23:15:00+00:00,2024-03-01T23:15:00+00:00,\"Odd, \"\"zone\"\"\",2460371.46875,62.5,Waning Gibbous",
+            ]
+        );
+    }
+
+    #[test]
+    fn tsv_separates_fields_with_tabs() {
+        let tsv = delimited(&[row("Europe/London")], b'\t').unwrap();
+        let lines: Vec<_> = tsv.split_terminator("\r\n").collect();
+
+        assert_eq!(lines.len(), 2);
+        for line in lines {
+            assert_eq!(line.split('\t').count(), 7, "{line:?}");
+            assert!(!line.contains(','), "{line:?}");
+        }
+        // Spaces don't need quoting
+        assert!(tsv.ends_with("\tWaning Gibbous\r\n"));
+    }
+
+    #[test]
+    fn local_instant_skips_clock_changes_at_midnight() {
+        // Santiago springs forward from midnight to 1am
+        let tz: Tz = "America/Santiago".parse().unwrap();
+        let date = NaiveDate::from_ymd_opt(2026, 9, 6).unwrap();
+        let start = local_instant(&tz, date, NaiveTime::MIN).unwrap();
+        assert_eq!(start.naive_local(), date.and_hms_opt(1, 0, 0).unwrap());
+
+        // An event a couple of weeks later searches back across the change
+        assert!(
+            !moonrises_between(date, date + Duration::days(2), -33.45, -70.67, &tz)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn scheduled_events_carry_organizer_attendee_and_sequence() {
+        let attendee = scheduling_attendee(IcsMethod::Cancel, Some("moon@example.com"))
+            .unwrap()
+            .unwrap();
+        let mut event = icalendar::Event::new();
+        schedule(
+            &mut event,
+            IcsMethod::Cancel,
+            "uid@whynotcats.com",
+            Some(&attendee),
+        );
+        let event = event.done().to_string();
+
+        for line in [
+            "UID:uid@whynotcats.com",
+            "ORGANIZER;CN=whynotcats:mailto:moonrise@whynotcats.com",
+            "SEQUENCE:1",
+            "STATUS:CANCELLED",
+        ] {
+            assert!(event.contains(line), "{line} missing from {event}");
+        }
+        // Parameters come out in any order
+        let attendee = event
+            .lines()
+            .find(|line| line.starts_with("ATTENDEE;"))
+            .unwrap();
+        assert!(attendee.ends_with(":mailto:moon@example.com"), "{attendee}");
+        assert!(attendee.contains("ROLE=REQ-PARTICIPANT"), "{attendee}");
+        assert!(attendee.contains("RSVP=FALSE"), "{attendee}");
+    }
+
+    #[test]
+    fn only_publish_goes_without_an_attendee() {
+        assert_eq!(scheduling_attendee(IcsMethod::Publish, None).unwrap(), None);
+        assert!(scheduling_attendee(IcsMethod::Request, None).is_err());
+        for attendee in ["moon", "moon@example", "a@b.com\r\nX-EVIL:1", "a;b@c.com"] {
+            assert!(
+                scheduling_attendee(IcsMethod::Request, Some(attendee)).is_err(),
+                "{attendee:?}"
+            );
+        }
+    }
+}
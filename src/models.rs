@@ -1,5 +1,26 @@
-use serde::{Deserialize, Serialize};
+use crate::astro::{
+    self, cultural_names::Culture, moonrise::EphemerisType, refraction::RefractionModel,
+};
+use crate::statistics::{GroupBy, StatisticsEntry};
+use crate::ApiError;
+use chrono::{DateTime, NaiveDate, Utc, Weekday};
+use elasticsearch::{
+    http::{
+        transport::{SingleNodeConnectionPool, TransportBuilder},
+        Url,
+    },
+    Elasticsearch,
+};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Location {
@@ -25,6 +46,25 @@ pub struct LocationResponse {
     pub elevation: Option<i64>,
     pub timezone: String,
     pub modification_date: String,
+    /// Only filled in when the search asks for `include_solar_today`
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub solar_today: Option<SolarToday>,
+    /// ES's explanation of the hit's relevance score, only for `explain` searches
+    #[serde(
+        rename = "_score_explanation",
+        skip_deserializing,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub score_explanation: Option<Value>,
+}
+
+/// Today's sunrise and sunset at a location, in the location's timezone.
+/// Both are `None` during polar day or night.
+#[derive(Debug, Serialize, Clone)]
+pub struct SolarToday {
+    pub sunrise: Option<String>,
+    pub sunset: Option<String>,
+    pub day_length_seconds: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -90,26 +130,1121 @@ impl LocationResponse {
             elevation: source["elevation"].as_i64(),
             timezone: source["timezone"].as_str().unwrap().to_string(),
             modification_date: source["modification_date"].as_str().unwrap().to_string(),
+            solar_today: None,
+            score_explanation: None,
         }
     }
 }
 
+/// Days of moonrises in a calendar when `number_of_days` isn't given
+const DEFAULT_CALENDAR_DAYS: usize = 30;
+
+fn default_calendar_days() -> usize {
+    DEFAULT_CALENDAR_DAYS
+}
+
+/// Every field but `lat`/`lon` has a default, so `/moonrise/ical_webcal` links
+/// with only `lat`, `lon` and `days` subscribe
 #[derive(Deserialize, Clone)]
 pub struct CreateCalendar {
     pub lat: f64,
     pub lon: f64,
+    /// Minutes the event starts before the moonrise
+    #[serde(default)]
     pub before: usize,
+    /// Minutes the event ends after the moonrise
+    #[serde(default)]
     pub after: usize,
+    #[serde(
+        alias = "days",
+        default = "default_calendar_days",
+        deserialize_with = "bounded_days"
+    )]
     pub number_of_days: usize,
     pub summary: Option<String>,
     pub timezone: Option<String>,
+    pub ics_method: Option<IcsMethod>,
+    /// Email address a `REQUEST` or `CANCEL` is sent to, both need one
+    pub attendee: Option<String>,
+    /// Add a reminder event for this many minutes, at most 1440, leading up to each moonrise event
+    pub before_warning_minutes: Option<u64>,
+    /// Add a follow-up event for this many minutes, at most 1440, after each moonrise event
+    pub after_warning_minutes: Option<u64>,
+    /// Note in each description whether the Moon is near perigee or apogee
+    pub include_distance_category: Option<bool>,
+    /// Note in each description when the Moon sets before astronomical dawn
+    pub moon_sets_before_sunrise: Option<bool>,
+    /// Add a rough estimate of the next high tide, based on the M2 tidal cycle
+    pub include_tide_commentary: Option<bool>,
+    /// Return `X-Debug-*` headers tracing the calculation, needs the server's `--debug` flag
+    pub debug_mode: Option<bool>,
+    /// Name the full moon in the description of moonrises close to one
+    pub include_cultural_names: Option<bool>,
+    /// Naming tradition used by `include_cultural_names`, defaults to the Farmer's Almanac
+    pub culture: Option<Culture>,
+    /// NOAA or UKHO tide gauge station code, reserved for official tide predictions
+    pub tide_gauge_station: Option<String>,
+    /// Show moonrise events as busy (`TRANSP:OPAQUE`), defaults to free
+    pub block_time: Option<bool>,
+    /// Prepend a UTF-8 byte order mark for Outlook for Windows and older Excel.
+    /// RFC 5545 doesn't allow a BOM, but most modern clients ignore it.
+    pub utf8_bom: Option<bool>,
+    /// Seconds added to every moonrise to account for an obstructed local
+    /// horizon, between -3600 and 3600
+    pub horizon_depression_correction_seconds: Option<i64>,
+    /// How precisely moonrises are calculated, defaults to `approximate`
+    pub use_ephemeris: Option<EphemerisType>,
+    /// Atmospheric refraction at the horizon, defaults to `standard` (Bennett)
+    pub refraction_model: Option<RefractionModel>,
+}
+
+/// The iCal `METHOD` of the generated calendar, which tells the receiving
+/// client how to process the events
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum IcsMethod {
+    #[default]
+    Publish,
+    Request,
+    Cancel,
+}
+
+impl IcsMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IcsMethod::Publish => "PUBLISH",
+            IcsMethod::Request => "REQUEST",
+            IcsMethod::Cancel => "CANCEL",
+        }
+    }
+
+    /// Revision of the events, a moonrise is only ever sent and then
+    /// cancelled, so the cancellation is the one revision
+    pub fn sequence(&self) -> u32 {
+        match self {
+            IcsMethod::Publish | IcsMethod::Request => 0,
+            IcsMethod::Cancel => 1,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct MoonriseQuery {
+    pub lat: f64,
+    pub lon: f64,
+    #[serde(deserialize_with = "bounded_days")]
+    pub number_of_days: usize,
+    pub timezone: Option<String>,
+    /// Add the Julian Date and Modified Julian Date of each moonrise
+    pub include_astronomical_notation: Option<bool>,
+    /// Add the Moon's hour angle at each moonrise
+    pub include_astronomical_data: Option<bool>,
+    /// Add an estimate of the moonlight each moonrise will bring
+    pub include_illumination_data: Option<bool>,
+    /// A landmark to line moonrises up with, only used when both are given
+    pub landmark_lat: Option<f64>,
+    pub landmark_lon: Option<f64>,
+    pub azimuth_format: Option<AzimuthFormat>,
+    /// How precisely moonrises are calculated, defaults to `approximate`
+    pub use_ephemeris: Option<EphemerisType>,
+    /// Add an emoji of the Moon's phase at each moonrise
+    pub include_moon_emoji: Option<bool>,
+    /// Which emoji set `include_moon_emoji` uses, defaults to `coarse8`
+    pub emoji_resolution: Option<EmojiResolution>,
+    /// Add the magnetic declination and the moonrise's compass bearing
+    pub include_magnetic_declination: Option<bool>,
+}
+
+/// How finely moon phase emoji follow the synodic month
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmojiResolution {
+    /// The 8 Unicode moon phase emoji
+    #[default]
+    Coarse8,
+    /// One symbol for each day of the synodic month, see `astro::MOON_PHASE_EMOJI_29`
+    Fine29,
+}
+
+impl EmojiResolution {
+    pub fn emoji(&self, phase_fraction: f64) -> &'static str {
+        match self {
+            EmojiResolution::Coarse8 => {
+                astro::MOON_PHASE_EMOJI[astro::phase_index(phase_fraction, 8)]
+            }
+            EmojiResolution::Fine29 => {
+                astro::MOON_PHASE_EMOJI_29[astro::phase_index(phase_fraction, 29)]
+            }
+        }
+    }
+}
+
+/// How compass bearings are written out
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AzimuthFormat {
+    /// Decimal degrees east of north
+    #[default]
+    Decimal,
+    /// Degrees, minutes and seconds, `112°34'56"`
+    Dms,
+    /// The nearest of the 16 compass points, `ESE`
+    Cardinal16,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Azimuth {
+    Degrees(f64),
+    Formatted(String),
+}
+
+impl AzimuthFormat {
+    pub fn format(&self, degrees: f64) -> Azimuth {
+        match self {
+            AzimuthFormat::Decimal => Azimuth::Degrees(astro::round_bearing(degrees)),
+            AzimuthFormat::Dms => Azimuth::Formatted(astro::degrees_to_dms(degrees)),
+            AzimuthFormat::Cardinal16 => {
+                Azimuth::Formatted(astro::degrees_to_cardinal(degrees).to_string())
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MoonriseEvent {
+    pub timestamp: i64,
+    pub moonrise: String,
+    /// Whether the moonrise falls on the current date in the requested timezone
+    pub is_tonight: bool,
+    /// Whether the Moon is near perigee, near apogee or at an average distance
+    pub moon_distance_category: String,
+    /// Separation of the Sun and Moon in ecliptic longitude, small values rise close to the Sun.
+    /// Always present, both longitudes are defined at every moonrise.
+    pub sun_moon_angle_degrees: f64,
+    /// Compass bearing of the moonrise, written according to `azimuth_format`
+    pub azimuth: Azimuth,
+    /// How much later, or earlier when negative, the moonrise is than the
+    /// average for its calendar month over three years
+    pub delta_from_monthly_mean_seconds: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub julian_date: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified_julian_date: Option<f64>,
+    /// Local sidereal time minus the Moon's right ascension as it rises
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hour_angle_at_rise_degrees: Option<f64>,
+    /// Rough ground illuminance once the Moon is high, in thousandths of a lux
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub illuminance_millilux: Option<f64>,
+    /// Bearing, in degrees, from the observer to the landmark
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bearing_to_landmark: Option<f64>,
+    /// Bearing, in degrees, of the moonrise as seen from the landmark
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bearing_from_landmark_to_moonrise: Option<f64>,
+    /// From the set chosen by `emoji_resolution`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub moon_phase_emoji: Option<&'static str>,
+    /// Day of the synodic month, 0 to 28, given with the `fine29` emoji
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub moon_phase_index_29: Option<u8>,
+    /// Degrees from true to magnetic north, positive east and negative west.
+    /// Missing for moonrises outside the magnetic model's 2025 to 2029.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub magnetic_declination_degrees: Option<f64>,
+    /// Bearing of the moonrise from magnetic north, as read off a compass
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub magnetic_azimuth_degrees: Option<f64>,
+    /// Highest altitude the Moon reaches after rising, as it crosses the
+    /// meridian, `None` if it doesn't transit within a day
+    pub meridian_altitude_degrees: Option<f64>,
+    /// Whether the Moon stays below 20° all night
+    pub low_transit_warning: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MoonriseList {
+    /// Version of the schema served at `/moonrise/json_schema`
+    pub schema_version: &'static str,
+    pub timezone: String,
+    pub events: Vec<MoonriseEvent>,
+}
+
+#[derive(Deserialize)]
+pub struct MoonriseTableQuery {
+    pub lat: f64,
+    pub lon: f64,
+    #[serde(alias = "days", deserialize_with = "bounded_days")]
+    pub number_of_days: usize,
+    pub timezone: Option<String>,
+}
+
+/// A row of the spreadsheet friendly moonrise exports
+#[derive(Serialize, Debug)]
+pub struct MoonriseRow {
+    pub date: String,
+    pub moonrise_utc: String,
+    pub moonrise_local: String,
+    pub timezone: String,
+    pub julian_date: f64,
+    pub illumination_pct: f64,
+    pub phase_name: &'static str,
+}
+
+#[derive(Deserialize)]
+pub struct AzimuthQuery {
+    pub lat: f64,
+    pub lon: f64,
+    #[serde(deserialize_with = "bounded_days")]
+    pub number_of_days: usize,
+    pub timezone: Option<String>,
+    pub azimuth_format: Option<AzimuthFormat>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MoonriseAzimuth {
+    pub moonrise: String,
+    pub azimuth: Azimuth,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AzimuthList {
+    pub timezone: String,
+    pub azimuths: Vec<MoonriseAzimuth>,
+}
+
+#[derive(Deserialize)]
+pub struct SmsAlertQuery {
+    pub lat: f64,
+    pub lon: f64,
+    #[serde(alias = "days", deserialize_with = "bounded_days")]
+    pub number_of_days: usize,
+    /// E.164 phone number, `+447700900123`
+    pub phone: String,
+}
+
+#[derive(Serialize)]
+pub struct SmsSubscriptionAccepted {
+    pub message: &'static str,
+    pub subscription_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct DigestQuery {
+    pub lat: f64,
+    pub lon: f64,
+    pub timezone: Option<String>,
+    /// First day of the digest week, defaults to Monday
+    pub week_start_day: Option<Weekday>,
+}
+
+#[derive(Deserialize)]
+pub struct StatisticsQuery {
+    pub lat: f64,
+    pub lon: f64,
+    pub year: i32,
+    pub timezone: Option<String>,
+    pub group_by: Option<GroupBy>,
+    /// First day of each week when grouping by week, defaults to Monday
+    pub week_start_day: Option<Weekday>,
+}
+
+#[derive(Serialize)]
+pub struct StatisticsResponse {
+    pub year: i32,
+    pub timezone: String,
+    pub statistics: Vec<StatisticsEntry>,
+}
+
+/// Weights default to those of `PhotographyWeights`
+#[derive(Deserialize)]
+pub struct NotableNightsQuery {
+    pub lat: f64,
+    pub lon: f64,
+    pub year: i32,
+    pub timezone: Option<String>,
+    pub weight_illumination: Option<f64>,
+    pub weight_azimuth: Option<f64>,
+    pub weight_twilight: Option<f64>,
+    pub weight_glare: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct NotableNight {
+    pub moonrise: String,
+    pub photogenic_score: f64,
+    pub explanation: String,
+}
+
+#[derive(Serialize)]
+pub struct NotableNightsResponse {
+    pub year: i32,
+    pub timezone: String,
+    pub nights: Vec<NotableNight>,
+}
+
+#[derive(Deserialize)]
+pub struct OppositionQuery {
+    pub lat: f64,
+    pub lon: f64,
+    pub year: i32,
+    pub timezone: Option<String>,
+}
+
+/// A full moon rising close to sunset, so close to opposition
+#[derive(Serialize)]
+pub struct OppositionNight {
+    pub date: NaiveDate,
+    /// Negative when the Moon rises before the Sun sets
+    pub moonrise_offset_from_sunset_minutes: i64,
+    pub illumination_pct: f64,
+    pub distance_km: u32,
+}
+
+#[derive(Serialize)]
+pub struct OppositionResponse {
+    pub year: i32,
+    pub timezone: String,
+    pub nights: Vec<OppositionNight>,
+}
+
+#[derive(Deserialize)]
+pub struct EquinoxComparisonQuery {
+    pub lat: f64,
+    pub lon: f64,
+    pub timezone: Option<String>,
+}
+
+/// How moonrise times vary over the current year, seasons follow the hemisphere of `lat`
+#[derive(Serialize)]
+pub struct EquinoxComparison {
+    pub year: i32,
+    pub timezone: String,
+    pub earliest_moonrise: Option<String>,
+    pub latest_moonrise: Option<String>,
+    pub spring_equinox_moonrise: Option<String>,
+    pub summer_solstice_moonrise: Option<String>,
+    pub autumn_equinox_moonrise: Option<String>,
+    pub winter_solstice_moonrise: Option<String>,
+    /// Azimuth of the northernmost moonrise during a major lunar standstill
+    pub major_lunar_standstill_azimuth: Option<f64>,
+}
+
+#[derive(Deserialize)]
+pub struct FeedMetadataQuery {
+    pub lat: f64,
+    pub lon: f64,
+    #[serde(alias = "days", deserialize_with = "bounded_days")]
+    pub number_of_days: usize,
+    pub timezone: Option<String>,
+}
+
+/// The parameters of a calendar subscription, echoed back with some elaboration
+#[derive(Serialize)]
+pub struct FeedMetadata {
+    pub lat: f64,
+    pub lon: f64,
+    /// Nearest known place to the coordinates, `None` when search is unavailable
+    pub resolved_location: Option<String>,
+    pub timezone: String,
+    pub number_of_days: usize,
+    pub next_moonrise: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ForEventQuery {
+    pub lat: f64,
+    pub lon: f64,
+    pub event_date: NaiveDate,
+    pub timezone: Option<String>,
+}
+
+/// The moonrise on, or failing that closest to, an event's date
+#[derive(Serialize)]
+pub struct EventMoonrise {
+    pub event_date: NaiveDate,
+    pub moonrise: String,
+    pub moon_phase: &'static str,
+    pub illumination_pct: f64,
+    /// Hours from moonrise until the next moonset, `None` when the Moon doesn't set within a day
+    pub moon_visible_duration_hours: Option<f64>,
+    pub is_full_moon_night: bool,
+    /// `excellent`, `good`, `fair` or `poor`, from the photography score
+    pub quality_rating: &'static str,
+}
+
+/// Integer latitudes from `lat_range_start` to `lat_range_end`, inclusive
+#[derive(Deserialize)]
+pub struct LatitudeSensitivityQuery {
+    pub lon: f64,
+    pub lat_range_start: i32,
+    pub lat_range_end: i32,
+    /// UTC date of the moonrises
+    pub date: NaiveDate,
+}
+
+#[derive(Serialize)]
+pub struct LatitudeMoonrise {
+    pub lat: i32,
+    /// `None` when the Moon doesn't rise on the date at this latitude
+    pub moonrise_utc: Option<String>,
+    pub azimuth: Option<f64>,
+}
+
+#[derive(Deserialize)]
+pub struct EquatorialMountQuery {
+    pub lat: f64,
+    pub lon: f64,
+    /// RFC 3339 date and time to calculate for, defaults to now
+    pub date: Option<DateTime<Utc>>,
+}
+
+/// Polar alignment and tracking data for an equatorial mount pointed at the Moon
+#[derive(Serialize)]
+pub struct EquatorialMountData {
+    pub date: String,
+    pub local_sidereal_time: String,
+    /// Elevation of the celestial pole above the horizon, in degrees
+    pub polar_elevation: f64,
+    pub moon_ra: String,
+    pub moon_dec: String,
+    pub hour_angle: String,
+    /// In degrees
+    pub parallactic_angle: f64,
 }
 
 #[derive(Deserialize)]
 pub struct SearchQuery {
     pub query: String,
+    /// Give up on the search after this many seconds. The server wide ES timeout
+    /// still applies, so this can only shorten it.
+    pub timeout_seconds: Option<u64>,
+    /// Drop hits with a relevance score below this, overriding the server default
+    pub min_score: Option<f64>,
+    /// Comma separated GeoNames feature codes to restrict results to, see
+    /// <https://www.geonames.org/export/codes.html>. Common populated place codes:
+    /// `PPL` (populated place), `PPLA` (seat of a first-order admin division),
+    /// `PPLA2` (seat of a second-order admin division), `PPLC` (capital) and
+    /// `PPLX` (section of a populated place)
+    #[serde(default, deserialize_with = "comma_separated")]
+    pub feature_codes: Option<Vec<String>>,
+    pub response_format: Option<ResponseFormat>,
+    /// Restrict results to a map viewport, only applied when all four edges are given
+    pub bbox_north: Option<f64>,
+    pub bbox_south: Option<f64>,
+    pub bbox_east: Option<f64>,
+    pub bbox_west: Option<f64>,
+    /// Add today's sunrise, sunset and day length to each full result
+    pub include_solar_today: Option<bool>,
+    /// Include ES's relevance scoring explanation for each hit, needs the server's `--allow-explain` flag
+    pub explain: Option<bool>,
+    /// Leave out `latitude` and `longitude` when `false`, defaults to `true`
+    pub include_coordinates: Option<bool>,
+}
+
+impl SearchQuery {
+    pub fn bounding_box(&self) -> Result<Option<GeoBoundingBox>, ApiError> {
+        GeoBoundingBox::from_edges(
+            self.bbox_north,
+            self.bbox_south,
+            self.bbox_east,
+            self.bbox_west,
+        )
+    }
+}
+
+/// Either starts an export of every location matching `query` and the
+/// filters, or continues the export identified by `scroll_id`
+#[derive(Deserialize, Debug)]
+pub struct ScrollQuery {
+    pub scroll_id: Option<String>,
+    pub query: Option<String>,
+    pub min_score: Option<f64>,
+    #[serde(default, deserialize_with = "comma_separated")]
+    pub feature_codes: Option<Vec<String>>,
+    pub bbox_north: Option<f64>,
+    pub bbox_south: Option<f64>,
+    pub bbox_east: Option<f64>,
+    pub bbox_west: Option<f64>,
+    pub response_format: Option<ResponseFormat>,
+    pub include_coordinates: Option<bool>,
+}
+
+/// One page of a location export
+#[derive(Serialize)]
+pub struct ScrollPage {
+    /// Pass back to get the next page, `None` once the export is exhausted
+    pub scroll_id: Option<String>,
+    pub results: Vec<SearchResponseItem>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GeoBoundingBox {
+    pub north: f64,
+    pub south: f64,
+    pub east: f64,
+    pub west: f64,
+}
+
+impl GeoBoundingBox {
+    /// A box when all four edges are given, none when none are. West may be
+    /// east of east, for boxes crossing the antimeridian.
+    pub fn from_edges(
+        north: Option<f64>,
+        south: Option<f64>,
+        east: Option<f64>,
+        west: Option<f64>,
+    ) -> Result<Option<Self>, ApiError> {
+        let (north, south, east, west) = match (north, south, east, west) {
+            (None, None, None, None) => return Ok(None),
+            (Some(north), Some(south), Some(east), Some(west)) => (north, south, east, west),
+            _ => {
+                return Err(ApiError::BadRequest(
+                    "bbox_north, bbox_south, bbox_east and bbox_west must be given together"
+                        .to_string(),
+                ))
+            }
+        };
+
+        for (name, value, limit) in [
+            ("bbox_north", north, 90.0),
+            ("bbox_south", south, 90.0),
+            ("bbox_east", east, 180.0),
+            ("bbox_west", west, 180.0),
+        ] {
+            if !(-limit..=limit).contains(&value) {
+                return Err(ApiError::BadRequest(format!(
+                    "{} must be between -{1} and {1}",
+                    name, limit
+                )));
+            }
+        }
+        if north < south {
+            return Err(ApiError::BadRequest(
+                "bbox_north must not be south of bbox_south".to_string(),
+            ));
+        }
+
+        Ok(Some(GeoBoundingBox {
+            north,
+            south,
+            east,
+            west,
+        }))
+    }
+}
+
+/// How much of each location a search returns
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseFormat {
+    #[default]
+    Full,
+    Minimal,
+    IdOnly,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MinimalLocation {
+    pub id: String,
+    pub name: String,
+    pub country_code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+}
+
+/// A full location without its coordinates, for type-ahead suggestions
+#[derive(Serialize, Debug, Clone)]
+pub struct LocationSummary {
+    pub id: String,
+    pub name: String,
+    pub ascii_name: String,
+    pub feature_code: String,
+    pub country_code: String,
+    pub admin1: Option<String>,
+    pub admin2: Option<String>,
+    pub feature_class: Option<FeatureClass>,
+    pub population: Option<i64>,
+    pub elevation: Option<i64>,
+    pub timezone: String,
+    pub modification_date: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub solar_today: Option<SolarToday>,
+    #[serde(rename = "_score_explanation", skip_serializing_if = "Option::is_none")]
+    pub score_explanation: Option<Value>,
+}
+
+impl From<LocationResponse> for LocationSummary {
+    fn from(location: LocationResponse) -> Self {
+        LocationSummary {
+            id: location.id,
+            name: location.name,
+            ascii_name: location.ascii_name,
+            feature_code: location.feature_code,
+            country_code: location.country_code,
+            admin1: location.admin1,
+            admin2: location.admin2,
+            feature_class: location.feature_class,
+            population: location.population,
+            elevation: location.elevation,
+            timezone: location.timezone,
+            modification_date: location.modification_date,
+            solar_today: location.solar_today,
+            score_explanation: location.score_explanation,
+        }
+    }
+}
+
+/// A location search result, serialized according to the requested `ResponseFormat`
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum SearchResponseItem {
+    Full(Box<LocationResponse>),
+    Summary(Box<LocationSummary>),
+    Minimal(MinimalLocation),
+    Id(String),
+}
+
+impl SearchResponseItem {
+    pub fn new(
+        location: LocationResponse,
+        format: ResponseFormat,
+        include_coordinates: bool,
+    ) -> SearchResponseItem {
+        match format {
+            ResponseFormat::Full if include_coordinates => {
+                SearchResponseItem::Full(Box::new(location))
+            }
+            ResponseFormat::Full => SearchResponseItem::Summary(Box::new(location.into())),
+            ResponseFormat::Minimal => SearchResponseItem::Minimal(MinimalLocation {
+                id: location.id,
+                name: location.name,
+                country_code: location.country_code,
+                latitude: include_coordinates.then_some(location.latitude),
+                longitude: include_coordinates.then_some(location.longitude),
+            }),
+            ResponseFormat::IdOnly => SearchResponseItem::Id(location.id),
+        }
+    }
+}
+
+/// Optional restrictions applied on top of the text match of a location search
+#[derive(Default, Debug, Clone)]
+pub struct LocationFilters {
+    pub min_score: Option<f64>,
+    pub feature_codes: Option<Vec<String>>,
+    pub bounding_box: Option<GeoBoundingBox>,
+    pub explain: bool,
+}
+
+/// Most days of moonrises a request can ask for, each one is calculated on demand
+const MAX_NUMBER_OF_DAYS: usize = 366;
+
+/// `number_of_days`, rejecting requests for more than `MAX_NUMBER_OF_DAYS`
+fn bounded_days<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let days = usize::deserialize(deserializer)?;
+    if days > MAX_NUMBER_OF_DAYS {
+        return Err(serde::de::Error::custom(format!(
+            "number_of_days must be at most {}",
+            MAX_NUMBER_OF_DAYS
+        )));
+    }
+
+    Ok(days)
+}
+
+/// Query strings can't carry sequences, so lists are passed as `a,b,c`
+fn comma_separated<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value: Option<String> = Option::deserialize(deserializer)?;
+    Ok(value
+        .map(|value| {
+            value
+                .split(',')
+                .filter(|item| !item.trim().is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .filter(|items| !items.is_empty()))
+}
+
+#[derive(Deserialize)]
+pub struct BulkSearchQuery {
+    pub queries: Vec<String>,
+    pub limit_per_query: Option<usize>,
+    /// As for `SearchQuery`, can only shorten the server wide ES timeout
+    pub timeout_seconds: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct BulkSearchResponse {
+    pub results: BTreeMap<String, Vec<LocationResponse>>,
+    /// Queries ES failed to run, with the reason, these have no `results` entry
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub errors: BTreeMap<String, String>,
 }
 
 pub struct DBConnections {
     pub es: String,
+    pub es_timeout: Duration,
+    pub es_min_score: Option<f64>,
+    /// How long ES keeps an idle location export scroll open
+    pub scroll_timeout: Duration,
+    pub debug: bool,
+    pub allow_explain: bool,
+    /// Where clients reach this server, used to build links back to it
+    pub public_base_url: String,
+    pub metrics: Metrics,
+    pub monthly_means: MonthlyMeanCache,
+}
+
+/// Operational counters reported by `/api/status`
+pub struct Metrics {
+    pub started_at: Instant,
+    pub requests_total: AtomicU64,
+    /// Keyed by route, the write lock is only taken the first time a route is hit
+    pub requests_by_endpoint: RwLock<BTreeMap<String, AtomicU64>>,
+    pub sms_subscription_requests_total: AtomicU64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            started_at: Instant::now(),
+            requests_total: AtomicU64::new(0),
+            requests_by_endpoint: RwLock::new(BTreeMap::new()),
+            sms_subscription_requests_total: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn record_request(&self, endpoint: &str) {
+        self.requests_total.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(counter) = self.requests_by_endpoint.read().unwrap().get(endpoint) {
+            counter.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.requests_by_endpoint
+            .write()
+            .unwrap()
+            .entry(endpoint.to_string())
+            .or_insert_with(|| AtomicU64::new(0))
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn requests_by_endpoint(&self) -> BTreeMap<String, u64> {
+        self.requests_by_endpoint
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(endpoint, counter)| (endpoint.clone(), counter.load(Ordering::Relaxed)))
+            .collect()
+    }
+}
+
+/// Identifies a monthly mean moonrise time: `lat` and `lon` in ten
+/// thousandths of a degree, the timezone, the middle year averaged and the month
+pub type MonthlyMeanKey = (i64, i64, String, i32, u32);
+
+/// Largest number of monthly means kept before the cache is emptied
+const MONTHLY_MEAN_CACHE_CAPACITY: usize = 10_000;
+
+/// Lazily computed mean moonrise times of day for a calendar month, in seconds
+/// after local midnight. `None` when the Moon never rose over that period.
+#[derive(Default)]
+pub struct MonthlyMeanCache {
+    entries: Mutex<HashMap<MonthlyMeanKey, (Option<f64>, Instant)>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl MonthlyMeanCache {
+    pub fn get_or_insert_with(
+        &self,
+        key: MonthlyMeanKey,
+        compute: impl FnOnce() -> Option<f64>,
+    ) -> Option<f64> {
+        if let Some((mean, _)) = self.entries.lock().unwrap().get(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return *mean;
+        }
+
+        // Computed without holding the lock, a racing request only repeats the work
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let mean = compute();
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= MONTHLY_MEAN_CACHE_CAPACITY {
+            entries.clear();
+        }
+        entries.insert(key, (mean, Instant::now()));
+
+        mean
+    }
+
+    pub fn status(&self) -> CacheStatus {
+        let entries = self.entries.lock().unwrap();
+        let (hits, misses) = (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        );
+
+        CacheStatus {
+            size: entries.len(),
+            hit_rate: if hits + misses == 0 {
+                0.0
+            } else {
+                hits as f64 / (hits + misses) as f64
+            },
+            oldest_entry_age_seconds: entries
+                .values()
+                .map(|(_, inserted)| inserted.elapsed().as_secs())
+                .max(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct CacheStatus {
+    pub size: usize,
+    pub hit_rate: f64,
+    pub oldest_entry_age_seconds: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct ElasticsearchStatus {
+    pub reachable: bool,
+    pub response_time_ms: u128,
+}
+
+#[derive(Serialize)]
+pub struct RequestCounts {
+    pub total: u64,
+    pub by_endpoint: BTreeMap<String, u64>,
+}
+
+#[derive(Serialize)]
+pub struct ServerStatus {
+    pub uptime_seconds: u64,
+    pub elasticsearch: ElasticsearchStatus,
+    pub requests: RequestCounts,
+    pub monthly_mean_cache: CacheStatus,
+    pub sms_subscription_requests_total: u64,
+}
+
+impl DBConnections {
+    pub fn es_client(&self) -> Result<Elasticsearch, elasticsearch::Error> {
+        let pool = SingleNodeConnectionPool::new(Url::parse(&self.es)?);
+        let transport = TransportBuilder::new(pool)
+            .timeout(self.es_timeout)
+            .build()?;
+
+        Ok(Elasticsearch::new(transport))
+    }
+}
+
+/// Builds `LocationResponse` fixtures without going through ES.
+///
+/// `build` is only available once the country code and timezone are set:
+///
+/// ```ignore
+/// let london = LocationResponseBuilder::new("2643743", "London", 51.50853, -0.12574)
+///     .country_code("GB")
+///     .timezone("Europe/London")
+///     .build();
+/// ```
+#[cfg(test)]
+pub mod builder {
+    use super::LocationResponse;
+    use std::marker::PhantomData;
+
+    pub struct Missing;
+    pub struct Set;
+
+    pub struct LocationResponseBuilder<CountryCode, Timezone> {
+        location: LocationResponse,
+        state: PhantomData<(CountryCode, Timezone)>,
+    }
+
+    impl LocationResponseBuilder<Missing, Missing> {
+        pub fn new(id: &str, name: &str, latitude: f64, longitude: f64) -> Self {
+            LocationResponseBuilder {
+                location: LocationResponse {
+                    id: id.to_string(),
+                    name: name.to_string(),
+                    ascii_name: name.to_string(),
+                    latitude,
+                    longitude,
+                    feature_code: "PPL".to_string(),
+                    country_code: String::new(),
+                    admin1: None,
+                    admin2: None,
+                    feature_class: None,
+                    population: None,
+                    elevation: None,
+                    timezone: String::new(),
+                    modification_date: String::new(),
+                    solar_today: None,
+                    score_explanation: None,
+                },
+                state: PhantomData,
+            }
+        }
+    }
+
+    impl<T> LocationResponseBuilder<Missing, T> {
+        pub fn country_code(mut self, country_code: &str) -> LocationResponseBuilder<Set, T> {
+            self.location.country_code = country_code.to_string();
+            LocationResponseBuilder {
+                location: self.location,
+                state: PhantomData,
+            }
+        }
+    }
+
+    impl<C> LocationResponseBuilder<C, Missing> {
+        pub fn timezone(mut self, timezone: &str) -> LocationResponseBuilder<C, Set> {
+            self.location.timezone = timezone.to_string();
+            LocationResponseBuilder {
+                location: self.location,
+                state: PhantomData,
+            }
+        }
+    }
+
+    impl<C, T> LocationResponseBuilder<C, T> {
+        pub fn feature_code(mut self, feature_code: &str) -> Self {
+            self.location.feature_code = feature_code.to_string();
+            self
+        }
+
+        pub fn admin1(mut self, admin1: &str) -> Self {
+            self.location.admin1 = Some(admin1.to_string());
+            self
+        }
+
+        pub fn population(mut self, population: i64) -> Self {
+            self.location.population = Some(population);
+            self
+        }
+    }
+
+    impl LocationResponseBuilder<Set, Set> {
+        pub fn build(self) -> LocationResponse {
+            self.location
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::builder::LocationResponseBuilder;
+    use super::{GeoBoundingBox, MoonriseTableQuery, ResponseFormat, SearchResponseItem};
+    use serde_json::json;
+
+    fn london() -> super::LocationResponse {
+        LocationResponseBuilder::new("2643743", "London", 51.50853, -0.12574)
+            .country_code("GB")
+            .timezone("Europe/London")
+            .build()
+    }
+
+    #[test]
+    fn search_response_item_formats() {
+        let minimal = SearchResponseItem::new(london(), ResponseFormat::Minimal, true);
+        assert_eq!(
+            serde_json::to_value(minimal).unwrap(),
+            json!({
+                "id": "2643743",
+                "name": "London",
+                "country_code": "GB",
+                "latitude": 51.50853,
+                "longitude": -0.12574
+            })
+        );
+
+        let id_only = SearchResponseItem::new(london(), ResponseFormat::IdOnly, true);
+        assert_eq!(serde_json::to_value(id_only).unwrap(), json!("2643743"));
+
+        let without_coordinates = SearchResponseItem::new(london(), ResponseFormat::Full, false);
+        let without_coordinates = serde_json::to_value(without_coordinates).unwrap();
+        assert_eq!(without_coordinates["name"], "London");
+        assert!(without_coordinates.get("latitude").is_none());
+        assert!(without_coordinates.get("longitude").is_none());
+    }
+
+    #[test]
+    fn builder_defaults_optional_fields() {
+        let london = LocationResponseBuilder::new("2643743", "London", 51.50853, -0.12574)
+            .timezone("Europe/London")
+            .country_code("GB")
+            .build();
+
+        assert_eq!(london.ascii_name, "London");
+        assert_eq!(london.country_code, "GB");
+        assert_eq!(london.timezone, "Europe/London");
+        assert_eq!(london.feature_code, "PPL");
+        assert!(london.population.is_none());
+    }
+
+    #[test]
+    fn builder_sets_optional_fields() {
+        let london = LocationResponseBuilder::new("2643743", "London", 51.50853, -0.12574)
+            .country_code("GB")
+            .feature_code("PPLC")
+            .admin1("ENG")
+            .population(8961989)
+            .timezone("Europe/London")
+            .build();
+
+        assert_eq!(london.feature_code, "PPLC");
+        assert_eq!(london.admin1.as_deref(), Some("ENG"));
+        assert_eq!(london.population, Some(8961989));
+    }
+
+    #[test]
+    fn bounding_box_edges_are_validated() {
+        assert!(GeoBoundingBox::from_edges(None, None, None, None)
+            .unwrap()
+            .is_none());
+        // Crossing the antimeridian
+        assert!(
+            GeoBoundingBox::from_edges(Some(10.0), Some(-10.0), Some(-170.0), Some(170.0))
+                .unwrap()
+                .is_some()
+        );
+
+        for (north, south, east, west) in [
+            (Some(95.0), Some(0.0), Some(10.0), Some(0.0)),
+            (Some(10.0), Some(0.0), Some(190.0), Some(0.0)),
+            (Some(0.0), Some(10.0), Some(10.0), Some(0.0)),
+            (Some(10.0), Some(0.0), None, None),
+            (Some(f64::NAN), Some(0.0), Some(10.0), Some(0.0)),
+        ] {
+            assert!(
+                GeoBoundingBox::from_edges(north, south, east, west).is_err(),
+                "{north:?} {south:?} {east:?} {west:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn number_of_days_is_bounded() {
+        let query = |days: &str| {
+            serde_urlencoded::from_str::<MoonriseTableQuery>(&format!("lat=1&lon=2&days={days}"))
+        };
+
+        assert_eq!(query("366").unwrap().number_of_days, 366);
+        assert!(query("367").is_err());
+        assert!(query("18446744073709551615").is_err());
+    }
 }
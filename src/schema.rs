@@ -0,0 +1,172 @@
+//! JSON Schema documents describing the API's JSON responses
+
+/// Version of the `/moonrise/json` response format, bump it along with the
+/// `$id` below whenever the response changes shape
+pub const MOONRISE_SCHEMA_VERSION: &str = "12";
+
+/// JSON Schema for `/moonrise/json` responses
+pub const MOONRISE_JSON_SCHEMA: &str = r##"{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "$id": "https://api.whynotcats.com/schemas/moonrise/json/12",
+  "title": "Moonrise list",
+  "description": "Upcoming moonrises for a location",
+  "type": "object",
+  "required": ["schema_version", "timezone", "events"],
+  "additionalProperties": false,
+  "properties": {
+    "schema_version": {
+      "description": "Version of this schema the response follows",
+      "type": "string",
+      "const": "12"
+    },
+    "timezone": {
+      "description": "IANA timezone the local moonrise times are given in",
+      "type": "string",
+      "examples": ["Europe/London"]
+    },
+    "events": {
+      "type": "array",
+      "items": { "$ref": "#/$defs/event" }
+    }
+  },
+  "$defs": {
+    "event": {
+      "type": "object",
+      "required": [
+        "timestamp",
+        "moonrise",
+        "is_tonight",
+        "moon_distance_category",
+        "sun_moon_angle_degrees",
+        "azimuth",
+        "delta_from_monthly_mean_seconds",
+        "meridian_altitude_degrees",
+        "low_transit_warning"
+      ],
+      "additionalProperties": false,
+      "properties": {
+        "timestamp": {
+          "description": "Unix timestamp of the moonrise, in seconds",
+          "type": "integer",
+          "examples": [1792094220]
+        },
+        "moonrise": {
+          "description": "Local time of the moonrise as RFC 3339",
+          "type": "string",
+          "format": "date-time",
+          "examples": ["2026-10-14T07:37:00+01:00"]
+        },
+        "is_tonight": {
+          "description": "Whether the moonrise falls on the current date in the requested timezone",
+          "type": "boolean"
+        },
+        "moon_distance_category": {
+          "description": "Whether the Moon is near perigee, near apogee or at an average distance",
+          "type": "string",
+          "enum": ["perigee_region", "apogee_region", "average"]
+        },
+        "sun_moon_angle_degrees": {
+          "description": "Separation of the Sun and Moon in ecliptic longitude, from 0 at new moon to 180 at full moon",
+          "type": "number",
+          "minimum": 0,
+          "maximum": 180,
+          "examples": [47.3]
+        },
+        "azimuth": {
+          "description": "Compass bearing of the moonrise. Decimal degrees east of north by default, a string for the dms and cardinal16 azimuth formats",
+          "type": ["number", "string"],
+          "examples": [112.6, "112°34'56\"", "ESE"]
+        },
+        "delta_from_monthly_mean_seconds": {
+          "description": "Seconds later than the average moonrise for the calendar month over three years, negative when earlier. Null when there's no average",
+          "type": ["integer", "null"],
+          "examples": [1380]
+        },
+        "julian_date": {
+          "description": "Julian Date of the moonrise, to 5 decimal places. Only present with include_astronomical_notation",
+          "type": "number",
+          "examples": [2461327.09713]
+        },
+        "modified_julian_date": {
+          "description": "Modified Julian Date of the moonrise, to 5 decimal places. Only present with include_astronomical_notation",
+          "type": "number",
+          "examples": [61326.59713]
+        },
+        "hour_angle_at_rise_degrees": {
+          "description": "Local sidereal time minus the Moon's right ascension at moonrise, between -180 and 180. Only present with include_astronomical_data",
+          "type": "number",
+          "examples": [-97.42]
+        },
+        "illuminance_millilux": {
+          "description": "Rough ground illuminance from the Moon once it is high, in thousandths of a lux. Only present with include_illumination_data",
+          "type": "number",
+          "minimum": 0,
+          "examples": [243.7]
+        },
+        "bearing_to_landmark": {
+          "description": "Bearing from the observer to the landmark, in degrees east of north. Only present with landmark_lat and landmark_lon",
+          "type": "number",
+          "examples": [148.1]
+        },
+        "bearing_from_landmark_to_moonrise": {
+          "description": "Bearing of the moonrise as seen from the landmark, in degrees east of north. Only present with landmark_lat and landmark_lon",
+          "type": "number",
+          "examples": [112.6]
+        },
+        "moon_phase_emoji": {
+          "description": "Emoji of the Moon's phase, from the set chosen by emoji_resolution. Only present with include_moon_emoji",
+          "type": "string",
+          "examples": ["🌔", "🌔🌕"]
+        },
+        "moon_phase_index_29": {
+          "description": "Day of the synodic month from 0 at new moon to 28. Only present with include_moon_emoji and emoji_resolution=fine29",
+          "type": "integer",
+          "minimum": 0,
+          "maximum": 28
+        },
+        "magnetic_declination_degrees": {
+          "description": "Angle from true north to magnetic north from a simplified World Magnetic Model, positive east and negative west. Only present with include_magnetic_declination, for moonrises from 2025 to 2029 when the model is valid",
+          "type": "number",
+          "examples": [-12.7]
+        },
+        "magnetic_azimuth_degrees": {
+          "description": "Compass bearing of the moonrise from magnetic north, in degrees. Only present alongside magnetic_declination_degrees",
+          "type": "number",
+          "minimum": 0,
+          "exclusiveMaximum": 360
+        },
+        "meridian_altitude_degrees": {
+          "description": "Highest altitude the Moon reaches after rising, as it crosses the meridian, or null when it doesn't transit within a day",
+          "type": ["number", "null"],
+          "minimum": -90,
+          "maximum": 90,
+          "examples": [54.3]
+        },
+        "low_transit_warning": {
+          "description": "Whether the Moon's meridian altitude is below 20 degrees, where atmospheric distortion is severe",
+          "type": "boolean"
+        }
+      }
+    }
+  }
+}"##;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn moonrise_schema_matches_version() {
+        let schema: Value = serde_json::from_str(MOONRISE_JSON_SCHEMA).unwrap();
+
+        assert!(schema["$id"]
+            .as_str()
+            .unwrap()
+            .ends_with(&format!("/{}", MOONRISE_SCHEMA_VERSION)));
+        assert_eq!(
+            schema["properties"]["schema_version"]["const"],
+            MOONRISE_SCHEMA_VERSION
+        );
+    }
+}
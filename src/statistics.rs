@@ -0,0 +1,235 @@
+use chrono::{prelude::*, Duration};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    #[default]
+    Month,
+    Week,
+    Day,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum StatisticsPeriod {
+    Month {
+        month: u32,
+    },
+    Week {
+        week_number: u32,
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+    },
+    Day {
+        date: NaiveDate,
+    },
+}
+
+/// Aggregated moonrise times of day, formatted as local `HH:MM`
+#[derive(Serialize, Debug, Clone)]
+pub struct MoonriseStatistics {
+    pub count: usize,
+    pub mean: String,
+    pub earliest: String,
+    pub latest: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct StatisticsEntry {
+    #[serde(flatten)]
+    pub period: StatisticsPeriod,
+    #[serde(flatten)]
+    pub statistics: MoonriseStatistics,
+    /// Number of moonrises in each local hour of the day, every hour is present
+    pub by_hour: BTreeMap<u32, usize>,
+}
+
+/// Groups local moonrise times into periods and aggregates each period.
+///
+/// Weeks starting on a Monday carry their ISO 8601 week number, weeks
+/// starting on other days are numbered from the week containing the 1st of
+/// January of the moonrise's year.
+pub fn group_moonrises(
+    moonrises: &[DateTime<Tz>],
+    group_by: GroupBy,
+    week_start: Weekday,
+) -> Vec<StatisticsEntry> {
+    let mut groups: BTreeMap<NaiveDate, Vec<&DateTime<Tz>>> = BTreeMap::new();
+    for moonrise in moonrises {
+        let date = moonrise.date_naive();
+        let key = match group_by {
+            GroupBy::Month => date.with_day(1).unwrap(),
+            GroupBy::Week => date.week(week_start).first_day(),
+            GroupBy::Day => date,
+        };
+        groups.entry(key).or_default().push(moonrise);
+    }
+
+    groups
+        .into_iter()
+        .map(|(start, moonrises)| {
+            let period = match group_by {
+                GroupBy::Month => StatisticsPeriod::Month {
+                    month: start.month(),
+                },
+                GroupBy::Week => {
+                    let week_number = if week_start == Weekday::Mon {
+                        start.iso_week().week()
+                    } else {
+                        let first_week = NaiveDate::from_ymd_opt(moonrises[0].year(), 1, 1)
+                            .unwrap()
+                            .week(week_start)
+                            .first_day();
+                        ((start - first_week).num_days() / 7 + 1) as u32
+                    };
+                    StatisticsPeriod::Week {
+                        week_number,
+                        start_date: start,
+                        end_date: start + Duration::days(6),
+                    }
+                }
+                GroupBy::Day => StatisticsPeriod::Day { date: start },
+            };
+
+            StatisticsEntry {
+                period,
+                statistics: time_statistics(&moonrises),
+                by_hour: hour_histogram(&moonrises),
+            }
+        })
+        .collect()
+}
+
+pub fn hour_histogram(moonrises: &[&DateTime<Tz>]) -> BTreeMap<u32, usize> {
+    let mut histogram: BTreeMap<u32, usize> = (0..24).map(|hour| (hour, 0)).collect();
+    for moonrise in moonrises {
+        *histogram.entry(moonrise.hour()).or_default() += 1;
+    }
+
+    histogram
+}
+
+fn seconds_of_day(time: &DateTime<Tz>) -> u32 {
+    time.num_seconds_from_midnight()
+}
+
+fn format_seconds_of_day(seconds: u32) -> String {
+    format!("{:02}:{:02}", seconds / 3600, (seconds % 3600) / 60)
+}
+
+/// Mean local time of day of the moonrises, in seconds after midnight
+pub fn mean_seconds_of_day(moonrises: &[&DateTime<Tz>]) -> f64 {
+    // Moonrise times wrap around midnight, so average them on a circle
+    let (sin, cos) = moonrises.iter().fold((0.0, 0.0), |(sin, cos), m| {
+        let angle = seconds_of_day(m) as f64 / SECONDS_PER_DAY * std::f64::consts::TAU;
+        (sin + angle.sin(), cos + angle.cos())
+    });
+
+    sin.atan2(cos).rem_euclid(std::f64::consts::TAU) / std::f64::consts::TAU * SECONDS_PER_DAY
+}
+
+/// Signed difference between the moonrise's local time of day and `mean`, in
+/// seconds, taking the short way around midnight
+pub fn seconds_from_mean(moonrise: &DateTime<Tz>, mean: f64) -> i64 {
+    let difference = (seconds_of_day(moonrise) as f64 - mean).rem_euclid(SECONDS_PER_DAY);
+    if difference >= SECONDS_PER_DAY / 2.0 {
+        (difference - SECONDS_PER_DAY).round() as i64
+    } else {
+        difference.round() as i64
+    }
+}
+
+pub fn time_statistics(moonrises: &[&DateTime<Tz>]) -> MoonriseStatistics {
+    let seconds: Vec<u32> = moonrises.iter().map(|m| seconds_of_day(m)).collect();
+    let mean = mean_seconds_of_day(moonrises);
+
+    MoonriseStatistics {
+        count: seconds.len(),
+        mean: format_seconds_of_day(mean.round() as u32 % SECONDS_PER_DAY as u32),
+        earliest: format_seconds_of_day(*seconds.iter().min().unwrap_or(&0)),
+        latest: format_seconds_of_day(*seconds.iter().max().unwrap_or(&0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Tz> {
+        Tz::UTC
+            .with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn monday_weeks_use_iso_week_numbers() {
+        // 2024-12-30 is the Monday of ISO week 1 of 2025
+        let moonrises = [
+            utc(2024, 6, 12, 21, 0),
+            utc(2024, 12, 30, 10, 0),
+            utc(2025, 1, 1, 11, 30),
+        ];
+        let entries = group_moonrises(&moonrises, GroupBy::Week, Weekday::Mon);
+
+        let weeks: Vec<_> = entries
+            .iter()
+            .map(|entry| match entry.period {
+                StatisticsPeriod::Week {
+                    week_number,
+                    start_date,
+                    end_date,
+                } => (week_number, start_date, end_date, entry.statistics.count),
+                _ => panic!("expected a week: {:?}", entry.period),
+            })
+            .collect();
+        assert_eq!(
+            weeks,
+            [
+                (
+                    24,
+                    NaiveDate::from_ymd_opt(2024, 6, 10).unwrap(),
+                    NaiveDate::from_ymd_opt(2024, 6, 16).unwrap(),
+                    1
+                ),
+                (
+                    1,
+                    NaiveDate::from_ymd_opt(2024, 12, 30).unwrap(),
+                    NaiveDate::from_ymd_opt(2025, 1, 5).unwrap(),
+                    2
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn hour_histogram_has_every_local_hour() {
+        let tz: Tz = "America/New_York".parse().unwrap();
+        // 23:40 and 00:10 in New York
+        let late = utc(2024, 3, 1, 4, 40).with_timezone(&tz);
+        let early = utc(2024, 3, 1, 5, 10).with_timezone(&tz);
+        let histogram = hour_histogram(&[&late, &early, &early]);
+
+        assert_eq!(histogram.len(), 24);
+        assert_eq!(histogram[&23], 1);
+        assert_eq!(histogram[&0], 2);
+        assert_eq!(histogram.values().sum::<usize>(), 3);
+    }
+
+    #[test]
+    fn seconds_from_mean_wraps_around_midnight() {
+        let before = utc(2024, 3, 1, 23, 50);
+        let after = utc(2024, 3, 2, 0, 10);
+        let mean = mean_seconds_of_day(&[&before, &after]);
+
+        // The mean of 23:50 and 00:10 is midnight, not midday
+        assert!(!(1.0..=SECONDS_PER_DAY - 1.0).contains(&mean), "{mean}");
+        assert_eq!(seconds_from_mean(&before, mean), -600);
+        assert_eq!(seconds_from_mean(&after, mean), 600);
+        assert_eq!(seconds_from_mean(&after, 23.0 * 3600.0 + 50.0 * 60.0), 1200);
+    }
+}
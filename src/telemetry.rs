@@ -0,0 +1,95 @@
+//! Console logging, plus span export over OTLP when built with the `otel`
+//! feature and `OTEL_EXPORTER_OTLP_ENDPOINT` is set
+
+/// Keeps span export running, dropping it flushes any spans still buffered
+pub struct Telemetry {
+    #[cfg(feature = "otel")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+/// Installs the global tracing subscriber
+pub fn init() -> Telemetry {
+    #[cfg(feature = "otel")]
+    let otlp_error = match std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT") {
+        Some(_) => match otlp::init() {
+            Ok(provider) => {
+                return Telemetry {
+                    provider: Some(provider),
+                }
+            }
+            Err(err) => Some(err),
+        },
+        None => None,
+    };
+
+    tracing_subscriber::fmt::init();
+    // Only logged once there's a subscriber to log to
+    #[cfg(feature = "otel")]
+    if let Some(err) = otlp_error {
+        log::error!("unable to start OTLP span export: {}", err);
+    }
+
+    Telemetry {
+        #[cfg(feature = "otel")]
+        provider: None,
+    }
+}
+
+#[cfg(feature = "otel")]
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take() {
+            if let Err(err) = provider.shutdown() {
+                log::error!("unable to flush spans: {}", err);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "otel")]
+mod otlp {
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_otlp::{ExporterBuildError, SpanExporter};
+    use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+    use std::str::FromStr;
+    use tracing_subscriber::{
+        filter::{LevelFilter, Targets},
+        layer::SubscriberExt,
+        util::SubscriberInitExt,
+        Layer,
+    };
+
+    /// The console filter `fmt::init` builds, from `RUST_LOG` or else at info
+    fn console_filter() -> Targets {
+        std::env::var("RUST_LOG")
+            .ok()
+            .and_then(|targets| Targets::from_str(&targets).ok())
+            .unwrap_or_else(|| Targets::new().with_default(LevelFilter::INFO))
+    }
+
+    /// Layers the console output and the OTLP exporter. The exporter reads its
+    /// endpoint and headers from the standard `OTEL_EXPORTER_OTLP_*` variables.
+    pub fn init() -> Result<SdkTracerProvider, ExporterBuildError> {
+        let exporter = SpanExporter::builder().with_http().build()?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(env!("CARGO_PKG_NAME"))
+                    .build(),
+            )
+            .build();
+
+        tracing_subscriber::registry()
+            .with(tracing_subscriber::fmt::layer().with_filter(console_filter()))
+            // The request spans from `TraceLayer` are at debug
+            .with(
+                tracing_opentelemetry::layer()
+                    .with_tracer(provider.tracer(env!("CARGO_PKG_NAME")))
+                    .with_filter(LevelFilter::DEBUG),
+            )
+            .init();
+
+        Ok(provider)
+    }
+}